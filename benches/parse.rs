@@ -0,0 +1,21 @@
+#[macro_use]
+extern crate criterion;
+extern crate yarn_lock;
+
+use criterion::Criterion;
+
+use yarn_lock::Lockfile;
+
+fn parse(c: &mut Criterion) {
+    let content = include_str!("../yarn.lock.big");
+
+    c.bench_function("parse", move |b| b.iter(|| yarn_lock::parse(content).unwrap()));
+    c.bench_function("parse_entries", move |b| b.iter(|| yarn_lock::parse_entries(content).unwrap()));
+    c.bench_function("Lockfile::parse", move |b| b.iter(|| Lockfile::parse(content).unwrap()));
+
+    #[cfg(feature = "rayon")]
+    c.bench_function("parse_parallel", move |b| b.iter(|| yarn_lock::parse_parallel(content).unwrap()));
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,241 @@
+//! Migration of yarn v1 locks into a yarn berry (2+) `yarn.lock`.
+//!
+//! Berry checksums hash the zip archives in its cache, prefixed with the cache key,
+//! so they can't be derived from the v1 `integrity` and are left for `yarn install` to fill in.
+
+use semver::Version;
+use serde_json;
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use entry::LockEntry;
+use npm_semver::parse_reqs;
+
+const HEADER: &str = "# This file is generated by running \"yarn install\" inside your project.\n\
+                      # Manual changes might be lost - proceed with caution!\n";
+
+/// The lockfile format of yarn 4.
+const METADATA_VERSION: u32 = 8;
+const CACHE_KEY: &str = "10c0";
+
+/// Something that could not be carried over into the berry lockfile.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MigrationIssue {
+    /// The v1 integrity can't be turned into a berry checksum, yarn has to fetch the package again.
+    ChecksumDropped { locator: String },
+    /// The package does not come from a registry, its block was left out.
+    NotFromRegistry { name: String, resolved: Option<String> },
+}
+
+impl fmt::Display for MigrationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MigrationIssue::ChecksumDropped { ref locator } => {
+                write!(f, "{}: checksum needs to be recomputed by yarn", locator)
+            }
+            MigrationIssue::NotFromRegistry { ref name, ref resolved } => {
+                write!(f, "{}: can't migrate non registry source {:?}", name, resolved)
+            }
+        }
+    }
+}
+
+/// A berry lockfile and everything that got lost on the way.
+#[derive(Debug, Clone)]
+pub struct Migration {
+    pub lockfile: String,
+    pub issues:   Vec<MigrationIssue>,
+}
+
+/// Whether yarn's syml would quote this scalar.
+fn needs_quotes(s: &str) -> bool {
+    s.is_empty()
+        || s.starts_with(|c: char| c.is_whitespace() || "-?:,[]{}#&*!|>'\"%@`".contains(c))
+        || s.ends_with(char::is_whitespace)
+        || s.contains(':')
+        || s.contains(" #")
+        || s.contains(',')
+}
+
+/// Quotes like syml does, as a JSON string, which is valid YAML.
+fn maybe_quote(s: &str) -> String {
+    if needs_quotes(s) {
+        serde_json::to_string(s).unwrap_or_else(|_| s.to_string())
+    } else {
+        s.to_string()
+    }
+}
+
+fn is_registry_tarball(entry: &LockEntry) -> bool {
+    entry.resolved_url()
+        .map(|url| (url.scheme() == "https" || url.scheme() == "http") && url.path().contains("/-/"))
+        .unwrap_or(false)
+}
+
+/// Berry spells out the protocol, plain semver ranges are `npm:` ones.
+///
+/// Tags, git urls, `file:` and other protocols are kept as they are.
+fn with_protocol(range: &str) -> String {
+    if parse_reqs(range).is_some() {
+        format!("npm:{}", range)
+    } else {
+        range.to_string()
+    }
+}
+
+/// The package behind an alias like `npm:string-width@^4.2.0`, or the name itself.
+fn aliased_name<'r>(name: &'r str, range: &'r str) -> &'r str {
+    match range.strip_prefix("npm:") {
+        Some(target) => match target.get(1..).and_then(|rest| rest.find('@')) {
+            Some(at) => &target[..at + 1],
+            None => name,
+        },
+        None => name,
+    }
+}
+
+fn write_block(out: &mut String, name: &str, version: &Version, entries: &[&LockEntry]) {
+    // the ranges stay exactly as written, so the descriptors of dependents still match them
+    let mut descriptors = entries
+        .iter()
+        .flat_map(|entry| entry.descriptors.iter())
+        .map(|descriptor| format!("{}@{}", descriptor.name, with_protocol(&descriptor.range)))
+        .collect::<Vec<_>>();
+    descriptors.sort();
+    descriptors.dedup();
+    let package = entries[0].descriptors.first().map(|d| aliased_name(name, &d.range)).unwrap_or(name);
+
+    out.push_str(&format!("\n{}:\n", maybe_quote(&descriptors.join(", "))));
+    out.push_str(&format!("  version: {}\n", maybe_quote(&version.to_string())));
+    out.push_str(&format!("  resolution: {}\n", maybe_quote(&format!("{}@npm:{}", package, version))));
+
+    // berry lists optional dependencies with the others and marks them in `dependenciesMeta`
+    let entry = entries[0];
+    let dependencies = entry.dependencies.iter().chain(&entry.optional_dependencies).collect::<BTreeMap<_, _>>();
+    if !dependencies.is_empty() {
+        out.push_str("  dependencies:\n");
+        for (dependency, range) in dependencies {
+            out.push_str(&format!("    {}: {}\n", maybe_quote(dependency), maybe_quote(&with_protocol(range))));
+        }
+    }
+    if !entry.optional_dependencies.is_empty() {
+        out.push_str("  dependenciesMeta:\n");
+        for dependency in entry.optional_dependencies.keys() {
+            out.push_str(&format!("    {}:\n      optional: true\n", maybe_quote(dependency)));
+        }
+    }
+    out.push_str("  languageName: node\n");
+    out.push_str("  linkType: hard\n");
+}
+
+/// Converts the blocks of a v1 `yarn.lock` into a berry lockfile.
+///
+/// Blocks are merged by `name@version`, every descriptor gets the `npm:` protocol.
+pub fn to_berry(entries: &[LockEntry]) -> Migration {
+    let mut blocks: BTreeMap<(&str, Version), Vec<&LockEntry>> = BTreeMap::new();
+    let mut issues = Vec::new();
+
+    for entry in entries {
+        let name = entry.name().unwrap_or_default();
+        match entry.parsed_version() {
+            Some(version) if is_registry_tarball(entry) => blocks.entry((name, version)).or_default().push(entry),
+            _ => {
+                let issue = MigrationIssue::NotFromRegistry {
+                    name:     name.to_string(),
+                    resolved: entry.resolved.clone(),
+                };
+                if !issues.contains(&issue) {
+                    issues.push(issue);
+                }
+            }
+        }
+    }
+
+    let mut lockfile = String::from(HEADER);
+    lockfile.push_str(&format!(
+        "\n__metadata:\n  version: {}\n  cacheKey: {}\n",
+        METADATA_VERSION, CACHE_KEY
+    ));
+
+    for (&(name, ref version), entries) in &blocks {
+        write_block(&mut lockfile, name, version, entries);
+        if entries.iter().any(|entry| entry.integrity.is_some()) {
+            issues.push(MigrationIssue::ChecksumDropped {
+                locator: format!("{}@npm:{}", name, version),
+            });
+        }
+    }
+
+    Migration { lockfile, issues }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_entries;
+
+    #[test]
+    fn migrates_to_berry() {
+        let entries = parse_entries(r#"
+chalk@^2.0.0, "chalk@>=2.0.0 <3", chalk@2.4.2:
+  version "2.4.2"
+  resolved "https://registry.yarnpkg.com/chalk/-/chalk-2.4.2.tgz#cd42541677a54333cf541a49108c1432b44c9424"
+  integrity sha512-xyz
+  dependencies:
+    ansi-styles "^3.2.1"
+  optionalDependencies:
+    fsevents "~2.1.0"
+
+my-fork@^2.0.0:
+  version "2.4.2"
+  resolved "git+https://github.com/me/my-fork.git#abcdef"
+"#).unwrap();
+
+        let migration = to_berry(&entries);
+        assert_eq!(
+            migration.lockfile,
+            r#"# This file is generated by running "yarn install" inside your project.
+# Manual changes might be lost - proceed with caution!
+
+__metadata:
+  version: 8
+  cacheKey: 10c0
+
+"chalk@npm:2.4.2, chalk@npm:>=2.0.0 <3, chalk@npm:^2.0.0":
+  version: 2.4.2
+  resolution: "chalk@npm:2.4.2"
+  dependencies:
+    ansi-styles: "npm:^3.2.1"
+    fsevents: "npm:~2.1.0"
+  dependenciesMeta:
+    fsevents:
+      optional: true
+  languageName: node
+  linkType: hard
+"#
+        );
+        assert_eq!(migration.issues.len(), 2);
+        assert_eq!(migration.issues[1], MigrationIssue::ChecksumDropped { locator: "chalk@npm:2.4.2".into() });
+    }
+
+    #[test]
+    fn quotes_like_json() {
+        assert_eq!(maybe_quote("chalk"), "chalk");
+        assert_eq!(maybe_quote("a@npm:\"b\""), r#""a@npm:\"b\"""#);
+        assert_eq!(aliased_name("string-width-cjs", "npm:string-width@^4.2.0"), "string-width");
+        assert_eq!(aliased_name("@s/a", "npm:@s/b@^1.0.0"), "@s/b");
+        assert_eq!(aliased_name("a", "npm:"), "a");
+    }
+
+    #[test]
+    fn adds_npm_protocol_to_semver_ranges() {
+        assert_eq!(with_protocol("^1.0.0"), "npm:^1.0.0");
+        assert_eq!(with_protocol("*"), "npm:*");
+        assert_eq!(with_protocol("npm:string-width@^4.2.0"), "npm:string-width@^4.2.0");
+        assert_eq!(with_protocol("latest"), "latest");
+        assert_eq!(with_protocol("github:me/my-fork"), "github:me/my-fork");
+        assert_eq!(with_protocol("git+https://github.com/me/fork.git#abc"), "git+https://github.com/me/fork.git#abc");
+        assert_eq!(with_protocol("file:../local"), "file:../local");
+    }
+}
@@ -0,0 +1,817 @@
+//! `yarn-lock` command line tool.
+//!
+//! Exits with `1` if a subcommand found problems (duplicates, differences, unsatisfied requirements, ...)
+//! and with `2` if the lockfile could not be read at all.
+
+extern crate clap;
+#[macro_use]
+extern crate serde_json;
+extern crate yarn_lock;
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use serde_json::Value;
+
+use yarn_lock::berry::to_berry;
+use yarn_lock::diff::{diff, Change};
+use yarn_lock::Error;
+use yarn_lock::graph::{lock_id, Graph};
+use yarn_lock::hoist::Layout;
+use yarn_lock::installed::{self, Problem as InstallProblem};
+use yarn_lock::lint::{lint, Config, Rule};
+use yarn_lock::manifest::Manifest;
+use yarn_lock::npm_semver::parse_reqs;
+use yarn_lock::package_lock::to_package_lock;
+use yarn_lock::registry::{outdated, PackumentCache};
+use yarn_lock::render::{to_dot, to_mermaid, Options as RenderOptions};
+use yarn_lock::resolutions::{self, Problem as ResolutionProblem};
+use yarn_lock::resolve::{upgrade, Resolver};
+use yarn_lock::writer::to_yarn_lock;
+use yarn_lock::{open, parse_entries, DependencyLock, EntryReader, Format, LockEntry};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Whether the subcommand found something to complain about.
+type Outcome = Result<bool, Error>;
+
+fn path_arg() -> Arg<'static, 'static> {
+    Arg::with_name("PATH")
+        .help("lockfile or project folder, `-` reads a yarn.lock from stdin")
+        .default_value(".")
+}
+
+fn manifest_arg() -> Arg<'static, 'static> {
+    Arg::with_name("manifest")
+        .long("manifest")
+        .takes_value(true)
+        .help("package.json to use, defaults to the one next to the lockfile")
+}
+
+/// Like `open`, but `-` streams a `yarn.lock` from stdin, e.g. from `git show HEAD:yarn.lock`.
+fn open_locks(path: &str) -> Result<Vec<DependencyLock>, Error> {
+    if path != "-" {
+        return open(path);
+    }
+    let stdin = io::stdin();
+    let mut locks = Vec::new();
+    for entry in EntryReader::new(stdin.lock()) {
+        locks.extend(entry?.to_locks());
+    }
+    Ok(locks)
+}
+
+/// Like `open_locks`, but keeps the blocks of a `yarn.lock` as written.
+fn open_entries(path: &str) -> Result<Vec<LockEntry>, Error> {
+    if path == "-" {
+        let stdin = io::stdin();
+        return EntryReader::new(stdin.lock()).collect();
+    }
+    let (path, format) = Format::detect(Path::new(path));
+    if format != Format::Yarn {
+        return Ok(LockEntry::from_locks(&open(&path)?));
+    }
+    let mut content = String::new();
+    File::open(&path)
+        .and_then(|mut file| file.read_to_string(&mut content))
+        .map_err(Error::io(&path))?;
+    parse_entries(&content)
+}
+
+fn lock_json(lock: &DependencyLock) -> Value {
+    serde_json::to_value(lock).unwrap_or(Value::Null)
+}
+
+/// `{kind, name, old, new}`, with the versions before and after the change.
+fn change_json(change: &Change) -> Value {
+    fn strings<T: ToString>(versions: &[T]) -> Vec<String> {
+        versions.iter().map(ToString::to_string).collect()
+    }
+    let (kind, name, old, new) = match *change {
+        Change::Added { ref name, ref versions } => ("added", name, Vec::new(), strings(versions)),
+        Change::Removed { ref name, ref versions } => ("removed", name, strings(versions), Vec::new()),
+        Change::Changed { ref name, ref old, ref new } => ("changed", name, strings(old), strings(new)),
+        Change::Source { ref name, ref version } => ("source", name, vec![version.to_string()], vec![version.to_string()]),
+        Change::Dependencies { ref name, ref version } => {
+            ("dependencies", name, vec![version.to_string()], vec![version.to_string()])
+        }
+    };
+    json!({ "kind": kind, "name": name, "old": old, "new": new })
+}
+
+fn print_json(value: &Value) {
+    println!("{}", serde_json::to_string_pretty(value).unwrap_or_default());
+}
+
+/// The `--manifest` or the folder of the lockfile.
+fn manifest_path(matches: &ArgMatches) -> PathBuf {
+    let path = match matches.value_of("manifest") {
+        Some(manifest) => PathBuf::from(manifest),
+        None => {
+            let (lockfile, _) = Format::detect(Path::new(matches.value_of("PATH").unwrap_or(".")));
+            lockfile.parent().map(Path::to_owned).unwrap_or_default()
+        }
+    };
+    if path.as_os_str().is_empty() { PathBuf::from(".") } else { path }
+}
+
+fn open_manifest(matches: &ArgMatches) -> Result<Manifest, Error> {
+    Manifest::open(manifest_path(matches))
+}
+
+fn list(matches: &ArgMatches, json: bool) -> Outcome {
+    let locks = open_locks(matches.value_of("PATH").unwrap_or("."))?;
+    if json {
+        print_json(&Value::Array(locks.iter().map(lock_json).collect()));
+    } else {
+        let graph = Graph::new(&locks);
+        for lock in graph.nodes() {
+            println!("{}", lock_id(lock));
+        }
+    }
+    Ok(false)
+}
+
+fn why(matches: &ArgMatches, json: bool) -> Outcome {
+    let locks = open_locks(matches.value_of("PATH").unwrap_or("."))?;
+    let graph = Graph::new(&locks);
+    let name = matches.value_of("PACKAGE").unwrap_or_default();
+    let manifest = open_manifest(matches).ok();
+
+    let reasons = graph.versions(name)
+        .iter()
+        .map(|lock| {
+            let direct = manifest.as_ref().map(|manifest| {
+                manifest.dependencies.iter()
+                    .chain(&manifest.dev_dependencies)
+                    .chain(&manifest.optional_dependencies)
+                    .any(|(dependency, range)| {
+                        dependency == name
+                            && graph.resolve_range(dependency, range).map(|resolved| resolved.version == lock.version)
+                                == Some(true)
+                    })
+            });
+            let dependents = graph.dependents(lock).into_iter().map(lock_id).collect::<Vec<_>>();
+            (lock_id(lock), direct.unwrap_or(false), dependents)
+        })
+        .collect::<Vec<_>>();
+
+    if json {
+        print_json(&Value::Array(
+            reasons.iter()
+                .map(|&(ref id, direct, ref dependents)| json!({ "package": id, "direct": direct, "dependents": dependents }))
+                .collect(),
+        ));
+    } else {
+        for &(ref id, direct, ref dependents) in &reasons {
+            println!("{}", id);
+            if direct {
+                println!("  required by package.json");
+            }
+            for dependent in dependents {
+                println!("  required by {}", dependent);
+            }
+        }
+    }
+    if reasons.is_empty() {
+        eprintln!("{} is not locked", name);
+    }
+    Ok(reasons.is_empty())
+}
+
+fn dupes(matches: &ArgMatches, json: bool) -> Outcome {
+    let locks = open_locks(matches.value_of("PATH").unwrap_or("."))?;
+    let graph = Graph::new(&locks);
+    let duplicates = graph.duplicates()
+        .into_iter()
+        .map(|(name, versions)| {
+            let versions = versions
+                .iter()
+                .filter_map(|lock| lock.version.as_ref().map(ToString::to_string))
+                .collect::<Vec<_>>();
+            (name, versions)
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    if json {
+        print_json(&json!(duplicates));
+    } else {
+        for (name, versions) in &duplicates {
+            println!("{} {}", name, versions.join(", "));
+        }
+    }
+    Ok(!duplicates.is_empty())
+}
+
+fn diff_command(matches: &ArgMatches, json: bool) -> Outcome {
+    let old = open_locks(matches.value_of("OLD").unwrap_or_default())?;
+    let new = open_locks(matches.value_of("NEW").unwrap_or_default())?;
+    let changes = diff(&old, &new);
+
+    if json {
+        print_json(&Value::Array(changes.iter().map(change_json).collect()));
+    } else {
+        for change in &changes {
+            println!("{}", change);
+        }
+    }
+    Ok(!changes.is_empty())
+}
+
+fn cycles(matches: &ArgMatches, json: bool) -> Outcome {
+    let locks = open_locks(matches.value_of("PATH").unwrap_or("."))?;
+    let graph = Graph::new(&locks);
+    let cycles = graph.cycles();
+    let ids = |locks: &[&DependencyLock]| locks.iter().map(|lock| lock_id(lock)).collect::<Vec<_>>();
+
+    if json {
+        print_json(&Value::Array(
+            cycles.iter()
+                .map(|cycle| json!({ "members": ids(&cycle.members), "path": ids(&cycle.path) }))
+                .collect(),
+        ));
+    } else {
+        for cycle in &cycles {
+            let path = ids(&cycle.path);
+            println!("{}", path.join(" -> "));
+            // members of the component the shortest cycle doesn't pass
+            let others = ids(&cycle.members).into_iter().filter(|id| !path.contains(id)).collect::<Vec<_>>();
+            if !others.is_empty() {
+                println!("  also {}", others.join(", "));
+            }
+        }
+    }
+    Ok(!cycles.is_empty())
+}
+
+fn check(matches: &ArgMatches, json: bool) -> Outcome {
+    let locks = open_locks(matches.value_of("PATH").unwrap_or("."))?;
+    let graph = Graph::new(&locks);
+    let manifest = open_manifest(matches)?;
+
+    let mut problems = manifest.dependencies
+        .iter()
+        .chain(&manifest.dev_dependencies)
+        .chain(&manifest.optional_dependencies)
+        .filter_map(|(name, range)| {
+            let versions = graph.versions(name);
+            if versions.is_empty() {
+                return Some((name.clone(), range.clone(), "not locked".to_string()));
+            }
+            match parse_reqs(range) {
+                Some(ref reqs) if !versions.iter().any(|lock| lock.satisfies(reqs)) => {
+                    Some((name.clone(), range.clone(), "no locked version satisfies the range".to_string()))
+                }
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>();
+    for problem in resolutions::check(&manifest, &graph).problems {
+        let (pattern, range) = match problem {
+            ResolutionProblem::Invalid { ref pattern, .. } | ResolutionProblem::Unused { ref pattern } => {
+                (pattern.clone(), manifest.resolutions.get(pattern).cloned().unwrap_or_default())
+            }
+            ResolutionProblem::Violated { ref pattern, ref range, .. } => (pattern.clone(), range.clone()),
+        };
+        problems.push((pattern, range, format!("resolution {}", problem)));
+    }
+
+    if json {
+        print_json(&Value::Array(
+            problems.iter()
+                .map(|(name, range, problem)| json!({ "name": name, "range": range, "problem": problem }))
+                .collect(),
+        ));
+    } else {
+        for (name, range, problem) in &problems {
+            println!("{}@{}: {}", name, range, problem);
+        }
+    }
+    Ok(!problems.is_empty())
+}
+
+fn stats(matches: &ArgMatches, json: bool) -> Outcome {
+    let locks = open_locks(matches.value_of("PATH").unwrap_or("."))?;
+    let graph = Graph::new(&locks);
+    let nodes = graph.nodes();
+    let hosts = nodes.iter()
+        .filter_map(|lock| lock.resolved.as_ref().and_then(|url| url.host_str().map(ToString::to_string)))
+        .collect::<BTreeSet<_>>();
+
+    let stats = json!({
+        "descriptors": locks.len(),
+        "packages": nodes.iter().map(|lock| &lock.name).collect::<BTreeSet<_>>().len(),
+        "versions": nodes.len(),
+        "duplicated": graph.duplicates().len(),
+        "without_integrity": nodes.iter().filter(|lock| lock.integrity.is_none()).count(),
+        "hosts": hosts,
+    });
+
+    if json {
+        print_json(&stats);
+    } else if let Value::Object(ref stats) = stats {
+        for (key, value) in stats {
+            println!("{:>18}: {}", key, value);
+        }
+    }
+    Ok(false)
+}
+
+fn export(matches: &ArgMatches) -> Outcome {
+    let path = matches.value_of("PATH").unwrap_or(".");
+    if matches.value_of("format") == Some("berry") {
+        let migration = to_berry(&open_entries(path)?);
+        print!("{}", migration.lockfile);
+        for issue in &migration.issues {
+            eprintln!("{}", issue);
+        }
+        return Ok(false);
+    }
+
+    let locks = open_locks(path)?;
+    match matches.value_of("format").unwrap_or("json") {
+        "package-lock" => print_json(&to_package_lock(&locks, &open_manifest(matches)?)),
+        format @ "dot" | format @ "mermaid" => {
+            let graph = Graph::new(&locks);
+            let options = RenderOptions {
+                root:       matches.value_of("package").map(ToString::to_string),
+                depth:      matches.value_of("depth").and_then(|depth| depth.parse().ok()),
+                collapse:   matches.is_present("collapse"),
+                duplicates: matches.is_present("duplicates"),
+            };
+            if let Some(ref root) = options.root {
+                if graph.versions(root).is_empty() {
+                    eprintln!("{} is not locked", root);
+                    return Ok(true);
+                }
+            }
+            print!("{}", if format == "dot" { to_dot(&graph, &options) } else { to_mermaid(&graph, &options) });
+        }
+        _ => print_json(&Value::Array(locks.iter().map(lock_json).collect())),
+    }
+    Ok(false)
+}
+
+fn hoist(matches: &ArgMatches, json: bool) -> Outcome {
+    let locks = open_locks(matches.value_of("PATH").unwrap_or("."))?;
+    let graph = Graph::new(&locks);
+    let path = manifest_path(matches);
+    let manifest = Manifest::open(&path)?;
+    let root = if path.is_dir() { path.as_path() } else { path.parent().unwrap_or_else(|| Path::new(".")) };
+    let workspaces = manifest.open_workspaces(root)?;
+    let layout = Layout::hoist_workspaces(&graph, &manifest, &workspaces);
+
+    let mut tree = BTreeMap::new();
+    for (location, lock) in &layout.packages {
+        let version = lock.version.as_ref().map(ToString::to_string).unwrap_or_default();
+        tree.insert(location.clone(), (lock.name.clone(), version, false));
+    }
+    for (location, target) in &layout.links {
+        let name = location.trim_start_matches("node_modules/").to_string();
+        tree.insert(location.clone(), (name, target.clone(), true));
+    }
+
+    if json {
+        let tree = tree.into_iter()
+            .map(|(location, (name, target, link))| {
+                let entry = if link {
+                    json!({ "name": name, "link": target })
+                } else {
+                    json!({ "name": name, "version": target })
+                };
+                (location, entry)
+            })
+            .collect();
+        print_json(&Value::Object(tree));
+    } else {
+        for (location, (_, target, link)) in tree {
+            if link {
+                println!("{} -> {}", location, target);
+            } else {
+                println!("{} {}", location, target);
+            }
+        }
+    }
+    Ok(false)
+}
+
+fn verify(matches: &ArgMatches, json: bool) -> Outcome {
+    let locks = open_locks(matches.value_of("PATH").unwrap_or("."))?;
+    let graph = Graph::new(&locks);
+    let path = manifest_path(matches);
+    let manifest = Manifest::open(&path)?;
+    let root = if path.is_dir() { path.as_path() } else { path.parent().unwrap_or_else(|| Path::new(".")) };
+    let workspaces = manifest.open_workspaces(root)?;
+    let tree = installed::read_tree(root, &workspaces)?;
+    let problems = installed::check(&graph, &manifest, &workspaces, &tree);
+
+    if json {
+        print_json(&Value::Array(
+            problems.iter()
+                .map(|problem| {
+                    let name = match *problem {
+                        InstallProblem::Missing { ref name, .. }
+                        | InstallProblem::WrongVersion { ref name, .. }
+                        | InstallProblem::Unlisted { ref name, .. }
+                        | InstallProblem::OtherSource { ref name, .. } => name,
+                    };
+                    json!({ "name": name, "problem": problem.to_string() })
+                })
+                .collect(),
+        ));
+    } else {
+        for problem in &problems {
+            println!("{}", problem);
+        }
+    }
+    Ok(!problems.is_empty())
+}
+
+fn fmt(matches: &ArgMatches) -> Outcome {
+    let (path, format) = Format::detect(Path::new(matches.value_of("PATH").unwrap_or(".")));
+    if format != Format::Yarn {
+        eprintln!("only yarn.lock files can be formatted");
+        return Ok(true);
+    }
+
+    let mut content = String::new();
+    File::open(&path)
+        .and_then(|mut file| file.read_to_string(&mut content))
+        .map_err(Error::io(&path))?;
+    let formatted = to_yarn_lock(&parse_entries(&content)?);
+
+    if matches.is_present("check") {
+        let changed = formatted != content;
+        if changed {
+            eprintln!("{:?} is not formatted", path);
+        }
+        Ok(changed)
+    } else if matches.is_present("write") {
+        File::create(&path)
+            .and_then(|mut file| file.write_all(formatted.as_bytes()))
+            .map_err(Error::io(&path))?;
+        Ok(false)
+    } else {
+        print!("{}", formatted);
+        Ok(false)
+    }
+}
+
+fn lint_command(matches: &ArgMatches, json: bool) -> Outcome {
+    let (path, format) = Format::detect(Path::new(matches.value_of("PATH").unwrap_or(".")));
+    if format != Format::Yarn {
+        eprintln!("only yarn.lock files can be linted");
+        return Ok(true);
+    }
+
+    let rules = |name| -> Vec<Rule> {
+        matches.values_of(name).into_iter().flatten().filter_map(|rule| rule.parse().ok()).collect()
+    };
+    let mut config = if matches.is_present("all") { Config::all() } else { Config::default() };
+    if matches.is_present("rule") {
+        config.rules = rules("rule").into_iter().collect();
+    }
+    for rule in rules("disable") {
+        config.rules.remove(&rule);
+    }
+    if let Some(hosts) = matches.values_of("allow-host") {
+        config.allowed_hosts = hosts.map(ToString::to_string).collect();
+    }
+    if let Some(max) = matches.value_of("max-versions").and_then(|max| max.parse().ok()) {
+        config.max_versions = max;
+    }
+    config.workspace = path.parent().and_then(|parent| {
+        let parent = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+        parent.canonicalize().ok()
+    });
+
+    let mut content = String::new();
+    File::open(&path)
+        .and_then(|mut file| file.read_to_string(&mut content))
+        .map_err(Error::io(&path))?;
+    let lints = lint(&content, &config)?;
+
+    if json {
+        print_json(&Value::Array(
+            lints.iter()
+                .map(|lint| {
+                    json!({
+                        "rule": lint.rule.name(),
+                        "line": lint.span.line,
+                        "column": lint.span.column,
+                        "len": lint.span.len,
+                        "message": lint.message,
+                    })
+                })
+                .collect(),
+        ));
+    } else if matches.is_present("github") {
+        for lint in &lints {
+            println!(
+                "::error file={},line={},col={},title={}::{}",
+                path.display(), lint.span.line, lint.span.column, lint.rule, lint.message
+            );
+        }
+    } else {
+        for lint in &lints {
+            println!("{}:{}", path.display(), lint);
+        }
+    }
+    Ok(!lints.is_empty())
+}
+
+fn outdated_command(matches: &ArgMatches, json: bool) -> Outcome {
+    let entries = LockEntry::from_locks(&open_locks(matches.value_of("PATH").unwrap_or("."))?);
+    let mut cache = PackumentCache::new(matches.value_of("cache").unwrap_or_default());
+    let outdated = outdated(&entries, &mut cache)?;
+
+    if json {
+        let version = |version: &Option<_>| version.as_ref().map(ToString::to_string);
+        print_json(&Value::Array(
+            outdated.iter()
+                .map(|report| {
+                    json!({
+                        "name": report.name,
+                        "range": report.range,
+                        "current": report.current.to_string(),
+                        "wanted": version(&report.wanted),
+                        "latest": version(&report.latest),
+                        "major": report.is_major(),
+                    })
+                })
+                .collect(),
+        ));
+    } else {
+        for report in &outdated {
+            println!("{}", report);
+        }
+    }
+    Ok(!outdated.is_empty())
+}
+
+fn resolve_command(matches: &ArgMatches) -> Outcome {
+    let (path, format) = Format::detect(Path::new(matches.value_of("PATH").unwrap_or(".")));
+    // entries of a yarn.lock are kept as written, so that git and tag descriptors are reused too
+    let locked = match format {
+        _ if !path.exists() => Vec::new(),
+        Format::Yarn => {
+            let mut content = String::new();
+            File::open(&path)
+                .and_then(|mut file| file.read_to_string(&mut content))
+                .map_err(Error::io(&path))?;
+            parse_entries(&content)?
+        }
+        _ => LockEntry::from_locks(&open(&path)?),
+    };
+    let manifest = open_manifest(matches)?;
+
+    let mut cache = PackumentCache::new(matches.value_of("cache").unwrap_or_default());
+    let mut resolver = Resolver::new(&mut cache, &locked);
+    for name in matches.values_of("upgrade").into_iter().flatten() {
+        resolver.upgrade(name);
+    }
+    let lockfile = to_yarn_lock(&resolver.resolve_manifest(&manifest)?);
+
+    if matches.is_present("write") {
+        let path = path.with_file_name(Format::Yarn.file_name());
+        File::create(&path)
+            .and_then(|mut file| file.write_all(lockfile.as_bytes()))
+            .map_err(Error::io(&path))?;
+    } else {
+        print!("{}", lockfile);
+    }
+    Ok(false)
+}
+
+fn upgrade_command(matches: &ArgMatches, json: bool) -> Outcome {
+    let (path, format) = Format::detect(Path::new(matches.value_of("PATH").unwrap_or(".")));
+    if format != Format::Yarn {
+        eprintln!("only yarn.lock files can be upgraded");
+        return Ok(true);
+    }
+    let mut content = String::new();
+    File::open(&path)
+        .and_then(|mut file| file.read_to_string(&mut content))
+        .map_err(Error::io(&path))?;
+    let entries = parse_entries(&content)?;
+
+    let name = matches.value_of("PACKAGE").unwrap_or_default();
+    let version = matches.value_of("VERSION").unwrap_or_default();
+    let mut cache = PackumentCache::new(matches.value_of("cache").unwrap_or_default());
+    let target = cache.resolve(name, version)?.cloned().ok_or_else(|| Error::Unresolved {
+        name:  name.to_string(),
+        range: version.to_string(),
+    })?;
+    let manifest = open_manifest(matches)?;
+    let upgraded = upgrade(&entries, name, &target, &mut cache, Some(&manifest))?;
+
+    if matches.is_present("write") {
+        File::create(&path)
+            .and_then(|mut file| file.write_all(to_yarn_lock(&upgraded.entries).as_bytes()))
+            .map_err(Error::io(&path))?;
+    }
+    if json {
+        print_json(&Value::Array(upgraded.changes.iter().map(change_json).collect()));
+    } else {
+        for change in &upgraded.changes {
+            println!("{}", change);
+        }
+    }
+    Ok(false)
+}
+
+fn run(matches: &ArgMatches) -> Outcome {
+    let json = matches.is_present("json");
+    match matches.subcommand() {
+        ("list", Some(matches)) => list(matches, json),
+        ("why", Some(matches)) => why(matches, json),
+        ("dupes", Some(matches)) => dupes(matches, json),
+        ("diff", Some(matches)) => diff_command(matches, json),
+        ("cycles", Some(matches)) => cycles(matches, json),
+        ("check", Some(matches)) => check(matches, json),
+        ("stats", Some(matches)) => stats(matches, json),
+        ("export", Some(matches)) => export(matches),
+        ("hoist", Some(matches)) => hoist(matches, json),
+        ("verify", Some(matches)) => verify(matches, json),
+        ("fmt", Some(matches)) => fmt(matches),
+        ("lint", Some(matches)) => lint_command(matches, json),
+        ("outdated", Some(matches)) => outdated_command(matches, json),
+        ("resolve", Some(matches)) => resolve_command(matches),
+        ("upgrade", Some(matches)) => upgrade_command(matches, json),
+        _ => unreachable!(),
+    }
+}
+
+fn main() {
+    let rule_names = Rule::all().iter().map(|rule| rule.name()).collect::<Vec<_>>();
+    let matches = App::new("yarn-lock")
+        .about("inspects yarn.lock, package-lock.json and pnpm-lock.yaml files")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(Arg::with_name("json").long("json").global(true).help("print JSON"))
+        .subcommand(SubCommand::with_name("list").about("lists all locked packages").arg(path_arg()))
+        .subcommand(
+            SubCommand::with_name("why")
+                .about("shows why a package is locked")
+                .arg(Arg::with_name("PACKAGE").required(true))
+                .arg(path_arg())
+                .arg(manifest_arg()),
+        )
+        .subcommand(SubCommand::with_name("dupes").about("lists packages locked in several versions").arg(path_arg()))
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("compares two lockfiles")
+                .arg(Arg::with_name("OLD").required(true))
+                .arg(Arg::with_name("NEW").required(true)),
+        )
+        .subcommand(SubCommand::with_name("cycles").about("lists dependency cycles between locked packages").arg(path_arg()))
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("checks that the lockfile satisfies the package.json and its resolutions")
+                .arg(path_arg())
+                .arg(manifest_arg()),
+        )
+        .subcommand(SubCommand::with_name("stats").about("counts packages, versions and sources").arg(path_arg()))
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("converts the lockfile into another format")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["json", "package-lock", "berry", "dot", "mermaid"])
+                        .default_value("json"),
+                )
+                .arg(
+                    Arg::with_name("package")
+                        .long("package")
+                        .takes_value(true)
+                        .help("dot and mermaid: only draw what this package depends on"),
+                )
+                .arg(Arg::with_name("depth").long("depth").takes_value(true).help("dot and mermaid: edges to follow"))
+                .arg(Arg::with_name("collapse").long("collapse").help("dot and mermaid: one node per package name"))
+                .arg(
+                    Arg::with_name("duplicates")
+                        .long("duplicates")
+                        .help("dot and mermaid: highlight packages locked in several versions"),
+                )
+                .arg(path_arg())
+                .arg(manifest_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("hoist")
+                .about("shows the node_modules tree yarn would install, including workspaces")
+                .arg(path_arg())
+                .arg(manifest_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("verify")
+                .about("checks the installed node_modules against the lockfile")
+                .arg(path_arg())
+                .arg(manifest_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("fmt")
+                .about("formats a yarn.lock the way yarn does")
+                .arg(path_arg())
+                .arg(Arg::with_name("check").long("check").help("only report whether the file is formatted"))
+                .arg(Arg::with_name("write").long("write").conflicts_with("check").help("format the file in place")),
+        )
+        .subcommand(
+            SubCommand::with_name("lint")
+                .about("checks a yarn.lock against policy rules")
+                .arg(path_arg())
+                .arg(
+                    Arg::with_name("rule")
+                        .long("rule")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .possible_values(&rule_names)
+                        .help("only run these rules"),
+                )
+                .arg(Arg::with_name("all").long("all").conflicts_with("rule").help("run every rule"))
+                .arg(
+                    Arg::with_name("disable")
+                        .long("disable")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .possible_values(&rule_names)
+                        .help("skip these rules"),
+                )
+                .arg(
+                    Arg::with_name("allow-host")
+                        .long("allow-host")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("registry hosts for allowed-hosts, defaults to the yarn and npm registries"),
+                )
+                .arg(Arg::with_name("max-versions").long("max-versions").takes_value(true).help("limit for max-versions"))
+                .arg(Arg::with_name("github").long("github").help("print GitHub Actions annotations")),
+        )
+        .subcommand(
+            SubCommand::with_name("outdated")
+                .about("lists locked versions that are behind a local registry metadata cache")
+                .arg(path_arg())
+                .arg(
+                    Arg::with_name("cache")
+                        .long("cache")
+                        .takes_value(true)
+                        .required(true)
+                        .help("folder with a <name>.json packument per package"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("resolve")
+                .about("regenerates the yarn.lock from the package.json and a local registry metadata cache")
+                .arg(path_arg())
+                .arg(manifest_arg())
+                .arg(
+                    Arg::with_name("cache")
+                        .long("cache")
+                        .takes_value(true)
+                        .required(true)
+                        .help("folder with a <name>.json packument per package"),
+                )
+                .arg(
+                    Arg::with_name("upgrade")
+                        .long("upgrade")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("ignore the locked versions of this package"),
+                )
+                .arg(Arg::with_name("write").long("write").help("write the yarn.lock instead of printing it")),
+        )
+        .subcommand(
+            SubCommand::with_name("upgrade")
+                .about("moves one package to a new version and lists what changes in the yarn.lock")
+                .arg(Arg::with_name("PACKAGE").required(true))
+                .arg(Arg::with_name("VERSION").required(true))
+                .arg(path_arg())
+                .arg(manifest_arg())
+                .arg(
+                    Arg::with_name("cache")
+                        .long("cache")
+                        .takes_value(true)
+                        .required(true)
+                        .help("folder with a <name>.json packument per package"),
+                )
+                .arg(Arg::with_name("write").long("write").help("write the upgraded yarn.lock")),
+        )
+        .get_matches();
+
+    let code = match run(&matches) {
+        Ok(false) => 0,
+        Ok(true) => 1,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            2
+        }
+    };
+    process::exit(code);
+}
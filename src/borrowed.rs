@@ -0,0 +1,186 @@
+//! Zero-copy view on a `yarn.lock`.
+//!
+//! All names, ranges and urls are slices of the parsed content and every block is kept as one
+//! `Entry`, no matter how many headlines it has. Use `Lockfile::to_locks` to get owned
+//! `DependencyLock`s when needed.
+
+use semver::{Version, VersionReq};
+use url::Url;
+
+use std::collections::HashMap;
+
+use super::DependencyLock;
+use entry::{self, LockEntry};
+use error::Error;
+use lexer::{split_descriptors, Lexer, Line, Section};
+use npm_semver::parse_reqs;
+
+/// One requirement from a headline, like `chalk@^2.0.0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Descriptor<'a> {
+    pub name:  &'a str,
+    pub range: &'a str,
+}
+
+/// One block of a `yarn.lock`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Entry<'a> {
+    pub descriptors:           Vec<Descriptor<'a>>,
+    pub version:               Option<&'a str>,
+    pub resolved:              Option<&'a str>,
+    pub integrity:             Option<&'a str>,
+    pub dependencies:          Vec<(&'a str, &'a str)>,
+    pub optional_dependencies: Vec<(&'a str, &'a str)>,
+}
+
+/// A parsed `yarn.lock` that borrows from its content.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Lockfile<'a> {
+    pub entries: Vec<Entry<'a>>,
+}
+
+fn last_entry<'e, 'a>(entries: &'e mut Vec<Entry<'a>>, line: usize) -> Result<&'e mut Entry<'a>, Error> {
+    entries.last_mut().ok_or_else(|| Error::syntax(line, "a headline before the first field"))
+}
+
+impl<'a> Lockfile<'a> {
+    /// Parses content of a `yarn.lock` in one pass over its lines.
+    pub fn parse(content: &'a str) -> Result<Lockfile<'a>, Error> {
+        let mut entries: Vec<Entry<'a>> = Vec::new();
+        let mut section = Section::None;
+
+        for line in Lexer::new(content) {
+            let (number, line) = line?;
+            match line {
+                Line::Headline(headline) => {
+                    section = Section::None;
+                    entries.push(Entry {
+                        descriptors: split_descriptors(headline)
+                            .into_iter()
+                            .map(|(name, range)| Descriptor { name, range })
+                            .collect(),
+                        ..Entry::default()
+                    });
+                }
+                Line::Field(key, value) => {
+                    let entry = last_entry(&mut entries, number)?;
+                    section = Section::None;
+                    match key {
+                        "version" => entry.version = Some(value),
+                        "resolved" => entry.resolved = Some(value),
+                        "integrity" => entry.integrity = Some(value),
+                        _ => (),
+                    }
+                }
+                Line::Section(name) => {
+                    last_entry(&mut entries, number)?;
+                    section = Section::named(name);
+                }
+                Line::Entry(key, value) => {
+                    let entry = last_entry(&mut entries, number)?;
+                    match section {
+                        Section::Dependencies => entry.dependencies.push((key, value)),
+                        Section::OptionalDependencies => entry.optional_dependencies.push((key, value)),
+                        Section::Other => (),
+                        Section::None => return Err(Error::syntax(number, "a section like `dependencies:`")),
+                    }
+                }
+            }
+        }
+
+        Ok(Lockfile { entries })
+    }
+
+    /// Converts into owned `DependencyLock`s, one per descriptor, like `parse` does.
+    ///
+    /// Dependency ranges repeat a lot, each distinct one is only parsed once.
+    pub fn to_locks(&self) -> Vec<DependencyLock> {
+        let mut ranges = HashMap::new();
+        self.entries.iter().flat_map(|entry| entry.to_locks_with(&mut ranges)).collect()
+    }
+}
+
+impl<'a> Entry<'a> {
+    /// Copies into an owned `LockEntry`.
+    pub fn to_entry(&self) -> LockEntry {
+        let owned = |pairs: &[(&str, &str)]| pairs.iter().map(|&(k, v)| (k.to_string(), v.to_string())).collect();
+        LockEntry {
+            descriptors: self.descriptors
+                .iter()
+                .map(|descriptor| entry::Descriptor::new(descriptor.name, descriptor.range))
+                .collect(),
+            version: self.version.map(ToString::to_string),
+            resolved: self.resolved.map(ToString::to_string),
+            integrity: self.integrity.map(ToString::to_string),
+            dependencies: owned(&self.dependencies),
+            optional_dependencies: owned(&self.optional_dependencies),
+        }
+    }
+
+    /// Converts into owned `DependencyLock`s, one per descriptor.
+    pub fn to_locks(&self) -> Vec<DependencyLock> {
+        self.to_locks_with(&mut HashMap::new())
+    }
+
+    fn to_locks_with(&self, ranges: &mut HashMap<&'a str, Option<Vec<VersionReq>>>) -> Vec<DependencyLock> {
+        let version = self.version.and_then(|v| Version::parse(v).ok());
+        let resolved = self.resolved.and_then(|r| Url::parse(r).ok());
+        let integrity = self.integrity.map(ToString::to_string);
+        let dependencies = parse_pairs(&self.dependencies, ranges);
+        let optional_dependencies = parse_pairs(&self.optional_dependencies, ranges);
+
+        self.descriptors
+            .iter()
+            .map(|descriptor| DependencyLock {
+                name: descriptor.name.to_string(),
+                last_seen: VersionReq::parse(descriptor.range).ok(),
+                version: version.clone(),
+                resolved: resolved.clone(),
+                integrity: integrity.clone(),
+                dependencies: dependencies.clone(),
+                optional_dependencies: optional_dependencies.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Parses dependency ranges, remembering each distinct one in `ranges`.
+fn parse_pairs<'a>(
+    pairs: &[(&'a str, &'a str)],
+    ranges: &mut HashMap<&'a str, Option<Vec<VersionReq>>>,
+) -> HashMap<String, Vec<VersionReq>> {
+    pairs
+        .iter()
+        .filter_map(|&(name, range)| match *ranges.entry(range).or_insert_with(|| parse_reqs(range)) {
+            Some(ref reqs) => Some((name.to_string(), reqs.clone())),
+            None => {
+                error!("INVALID Depedency {} {}", name, range);
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::{parse, parse_entries};
+
+    #[test]
+    fn borrows_from_content() {
+        let content = include_str!("../yarn.lock.big");
+        let lockfile = Lockfile::parse(content).unwrap();
+        let first = &lockfile.entries[0];
+
+        assert_eq!(first.descriptors.len(), 2);
+        assert_eq!(first.version, Some("7.8.3"));
+        assert_eq!(first.dependencies, vec![("@babel/highlight", "^7.8.3")]);
+        assert_eq!(lockfile.to_locks(), parse(content).unwrap());
+        assert_eq!(lockfile.entries.iter().map(Entry::to_entry).collect::<Vec<_>>(), parse_entries(content).unwrap());
+    }
+
+    #[test]
+    fn rejects_bad_indentation() {
+        assert!(Lockfile::parse("a@^1.0.0:\n   version \"1.0.0\"\n").is_err());
+    }
+}
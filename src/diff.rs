@@ -0,0 +1,177 @@
+//! Differences between two sets of locks, e.g. before and after an upgrade.
+
+use semver::{Version, VersionReq};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use super::DependencyLock;
+use npm_semver::normalize;
+
+/// How one package changed between two lockfiles.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added { name: String, versions: Vec<Version> },
+    Removed { name: String, versions: Vec<Version> },
+    /// The set of locked versions changed.
+    Changed { name: String, old: Vec<Version>, new: Vec<Version> },
+    /// Same version, but it is now fetched from somewhere else or has a different hash.
+    Source { name: String, version: Version },
+    /// Same version and source, but the ranges of its dependencies mean something else.
+    Dependencies { name: String, version: Version },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn list(versions: &[Version]) -> String {
+            versions.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+        }
+        match *self {
+            Change::Added { ref name, ref versions } => write!(f, "+ {} {}", name, list(versions)),
+            Change::Removed { ref name, ref versions } => write!(f, "- {} {}", name, list(versions)),
+            Change::Changed { ref name, ref old, ref new } => {
+                write!(f, "~ {} {} -> {}", name, list(old), list(new))
+            }
+            Change::Source { ref name, ref version } => write!(f, "! {} {} changed its source", name, version),
+            Change::Dependencies { ref name, ref version } => {
+                write!(f, "! {} {} changed its dependencies", name, version)
+            }
+        }
+    }
+}
+
+type Sources<'a> = BTreeMap<&'a Version, &'a DependencyLock>;
+
+fn by_name(locks: &[DependencyLock]) -> BTreeMap<&str, Sources<'_>> {
+    let mut packages: BTreeMap<&str, Sources> = BTreeMap::new();
+    for lock in locks {
+        if let Some(ref version) = lock.version {
+            packages.entry(&lock.name).or_default().insert(version, lock);
+        }
+    }
+    packages
+}
+
+/// The dependencies of a lock with normalized ranges, so that respellings don't count as changes.
+///
+/// Optional dependencies are keyed apart, turning a dependency optional is a change as well.
+fn dependencies(lock: &DependencyLock) -> BTreeMap<(&str, bool), Vec<VersionReq>> {
+    let required = lock.dependencies.iter().map(|(name, reqs)| ((name.as_str(), false), reqs));
+    let optional = lock.optional_dependencies.iter().map(|(name, reqs)| ((name.as_str(), true), reqs));
+    required
+        .chain(optional)
+        .map(|(key, reqs)| (key, normalize(reqs).unwrap_or_else(|| reqs.clone())))
+        .collect()
+}
+
+/// Lists what changed from `old` to `new`, sorted by package name.
+pub fn diff(old: &[DependencyLock], new: &[DependencyLock]) -> Vec<Change> {
+    let old = by_name(old);
+    let new = by_name(new);
+    let names = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+    let versions = |sources: &Sources| sources.keys().map(|v| (*v).clone()).collect::<Vec<_>>();
+
+    let mut changes = Vec::new();
+    for name in names {
+        match (old.get(name), new.get(name)) {
+            (None, Some(new)) => changes.push(Change::Added { name: name.to_string(), versions: versions(new) }),
+            (Some(old), None) => changes.push(Change::Removed { name: name.to_string(), versions: versions(old) }),
+            (Some(old), Some(new)) => {
+                if old.keys().ne(new.keys()) {
+                    changes.push(Change::Changed {
+                        name: name.to_string(),
+                        old:  versions(old),
+                        new:  versions(new),
+                    });
+                }
+                for (version, old) in old {
+                    let new = match new.get(version) {
+                        Some(new) => new,
+                        None => continue,
+                    };
+                    let (name, version) = (name.to_string(), (*version).clone());
+                    if (&old.resolved, &old.integrity) != (&new.resolved, &new.integrity) {
+                        changes.push(Change::Source { name, version });
+                    } else if dependencies(old) != dependencies(new) {
+                        changes.push(Change::Dependencies { name, version });
+                    }
+                }
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn lock(name: &str, version: &str, integrity: &str) -> DependencyLock {
+        DependencyLock {
+            name:         name.into(),
+            last_seen:    None,
+            version:      Version::parse(version).ok(),
+            resolved:     None,
+            integrity:    Some(integrity.into()),
+            dependencies: HashMap::new(),
+            optional_dependencies: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn diffs_locks() {
+        let old = vec![lock("a", "1.0.0", "x"), lock("b", "1.0.0", "x"), lock("c", "1.0.0", "x")];
+        let new = vec![lock("a", "1.1.0", "x"), lock("c", "1.0.0", "y"), lock("d", "1.0.0", "x")];
+        let v = |v: &str| Version::parse(v).unwrap();
+
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                Change::Changed { name: "a".into(), old: vec![v("1.0.0")], new: vec![v("1.1.0")] },
+                Change::Removed { name: "b".into(), versions: vec![v("1.0.0")] },
+                Change::Source { name: "c".into(), version: v("1.0.0") },
+                Change::Added { name: "d".into(), versions: vec![v("1.0.0")] },
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_respelled_ranges() {
+        use npm_semver::parse_reqs;
+
+        let with_dependency = |range: &str| {
+            let mut lock = lock("a", "1.0.0", "x");
+            lock.dependencies.insert("b".into(), parse_reqs(range).unwrap());
+            vec![lock]
+        };
+        let old = with_dependency("^1.0.0");
+        assert!(diff(&old, &with_dependency(">= 1.0.0 < 2")).is_empty());
+        assert!(diff(&old, &with_dependency(">=1.0.0 <2.0.0-0")).is_empty());
+        assert_eq!(
+            diff(&old, &with_dependency("^1.1.0")),
+            vec![Change::Dependencies { name: "a".into(), version: Version::parse("1.0.0").unwrap() }]
+        );
+    }
+
+    #[test]
+    fn compares_optional_dependencies() {
+        use npm_semver::parse_reqs;
+
+        let with_optional = |dependency: &str, range: &str| {
+            let mut lock = lock("a", "1.0.0", "x");
+            lock.optional_dependencies.insert(dependency.into(), parse_reqs(range).unwrap());
+            vec![lock]
+        };
+        let old = with_optional("fsevents", "^1.2.0");
+        let changed = vec![Change::Dependencies { name: "a".into(), version: Version::parse("1.0.0").unwrap() }];
+        assert!(diff(&old, &with_optional("fsevents", ">=1.2.0 <2")).is_empty());
+        assert_eq!(diff(&old, &with_optional("fsevents", "^2.0.0")), changed);
+        assert_eq!(diff(&old, &with_optional("chokidar", "^1.2.0")), changed);
+
+        let mut required = lock("a", "1.0.0", "x");
+        required.dependencies.insert("fsevents".into(), parse_reqs("^1.2.0").unwrap());
+        assert_eq!(diff(&old, &[required]), changed);
+    }
+}
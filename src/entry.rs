@@ -0,0 +1,214 @@
+//! Block level model of a `yarn.lock`, close to yarn's own.
+//!
+//! One `LockEntry` is one block, with all descriptors of its headline and every value as written.
+//! `DependencyLock`s are a view on top of it, one per descriptor.
+
+use semver::{Version, VersionReq};
+use url::Url;
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::slice;
+use std::str::FromStr;
+
+use super::DependencyLock;
+use error::Error;
+use lexer::split_descriptor;
+use npm_semver::{format_reqs, parse_reqs};
+
+/// A requirement from a headline, like `chalk@^2.0.0`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
+pub struct Descriptor {
+    pub name:  String,
+    pub range: String,
+}
+
+impl Descriptor {
+    pub fn new<N: Into<String>, R: Into<String>>(name: N, range: R) -> Descriptor {
+        Descriptor { name: name.into(), range: range.into() }
+    }
+}
+
+impl fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}@{}", self.name, self.range)
+    }
+}
+
+impl FromStr for Descriptor {
+    type Err = ();
+
+    /// Splits at the first `@` after the name, the leading one of a scope belongs to the name.
+    fn from_str(s: &str) -> Result<Descriptor, ()> {
+        match split_descriptor(s) {
+            ("", _) => Err(()),
+            (name, range) => Ok(Descriptor::new(name, range)),
+        }
+    }
+}
+
+/// One block of a `yarn.lock`.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
+pub struct LockEntry {
+    /// Everything in the headline that resolves to this entry.
+    pub descriptors:           Vec<Descriptor>,
+    pub version:               Option<String>,
+    pub resolved:              Option<String>,
+    pub integrity:             Option<String>,
+    pub dependencies:          BTreeMap<String, String>,
+    pub optional_dependencies: BTreeMap<String, String>,
+}
+
+fn parse_dependencies(dependencies: &BTreeMap<String, String>) -> HashMap<String, Vec<VersionReq>> {
+    dependencies
+        .iter()
+        .filter_map(|(name, range)| match parse_reqs(range) {
+            Some(reqs) => Some((name.clone(), reqs)),
+            None => {
+                error!("INVALID Depedency {} {}", name, range);
+                None
+            }
+        })
+        .collect()
+}
+
+fn format_dependencies(dependencies: &HashMap<String, Vec<VersionReq>>) -> BTreeMap<String, String> {
+    dependencies.iter().map(|(name, reqs)| (name.clone(), format_reqs(reqs))).collect()
+}
+
+impl LockEntry {
+    /// The name of the package, taken from the first descriptor.
+    pub fn name(&self) -> Option<&str> {
+        self.descriptors.first().map(|descriptor| descriptor.name.as_str())
+    }
+
+    pub fn parsed_version(&self) -> Option<Version> {
+        self.version.as_ref().and_then(|v| Version::parse(v).ok())
+    }
+
+    pub fn resolved_url(&self) -> Option<Url> {
+        self.resolved.as_ref().and_then(|r| Url::parse(r).ok())
+    }
+
+    /// Checks that `version` and `resolved` can be parsed, `to_locks` silently drops them otherwise.
+    pub fn validate(&self) -> Result<(), Error> {
+        if let Some(ref version) = self.version {
+            Version::parse(version).map_err(|_| Error::InvalidVersion { value: version.clone() })?;
+        }
+        if let Some(ref resolved) = self.resolved {
+            Url::parse(resolved).map_err(|source| Error::InvalidUrl { value: resolved.clone(), source })?;
+        }
+        Ok(())
+    }
+
+    /// The `dependencies` with parsed ranges, invalid ones are left out.
+    pub fn dependency_reqs(&self) -> HashMap<String, Vec<VersionReq>> {
+        parse_dependencies(&self.dependencies)
+    }
+
+    /// The `optionalDependencies` with parsed ranges, invalid ones are left out.
+    pub fn optional_dependency_reqs(&self) -> HashMap<String, Vec<VersionReq>> {
+        parse_dependencies(&self.optional_dependencies)
+    }
+
+    /// One `DependencyLock` per descriptor.
+    pub fn to_locks(&self) -> Vec<DependencyLock> {
+        let version = self.parsed_version();
+        let resolved = self.resolved_url();
+        let dependencies = self.dependency_reqs();
+        let optional_dependencies = self.optional_dependency_reqs();
+
+        self.descriptors
+            .iter()
+            .map(|descriptor| DependencyLock {
+                name:         descriptor.name.clone(),
+                last_seen:    VersionReq::parse(&descriptor.range).ok(),
+                version:      version.clone(),
+                resolved:     resolved.clone(),
+                integrity:    self.integrity.clone(),
+                dependencies: dependencies.clone(),
+                optional_dependencies: optional_dependencies.clone(),
+            })
+            .collect()
+    }
+
+    /// Groups locks of the same `name@version` and source back into entries.
+    ///
+    /// Ranges are rebuilt from `last_seen`, so they may be spelled differently than in the original file.
+    pub fn from_locks(locks: &[DependencyLock]) -> Vec<LockEntry> {
+        let mut entries: BTreeMap<(&str, Option<&Version>, Option<&Url>), LockEntry> = BTreeMap::new();
+
+        for lock in locks {
+            let key = (lock.name.as_str(), lock.version.as_ref(), lock.resolved.as_ref());
+            let entry = entries.entry(key).or_insert_with(|| LockEntry {
+                descriptors: Vec::new(),
+                version: lock.version.as_ref().map(ToString::to_string),
+                resolved: lock.resolved.as_ref().map(ToString::to_string),
+                integrity: lock.integrity.clone(),
+                dependencies: format_dependencies(&lock.dependencies),
+                optional_dependencies: format_dependencies(&lock.optional_dependencies),
+            });
+
+            let range = match (lock.last_seen.as_ref(), lock.version.as_ref()) {
+                (Some(req), _) => format_reqs(slice::from_ref(req)),
+                (None, Some(version)) => version.to_string(),
+                (None, None) => "*".into(),
+            };
+            let descriptor = Descriptor::new(lock.name.clone(), range);
+            if !entry.descriptors.contains(&descriptor) {
+                entry.descriptors.push(descriptor);
+            }
+        }
+
+        entries.into_values().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_descriptors() {
+        assert_eq!("@babel/core@^7.0.0".parse(), Ok(Descriptor::new("@babel/core", "^7.0.0")));
+        assert_eq!("fstream@>= 0.1.30 < 1".parse(), Ok(Descriptor::new("fstream", ">= 0.1.30 < 1")));
+        assert_eq!("@babel/core@^7.0.0".parse::<Descriptor>().unwrap().to_string(), "@babel/core@^7.0.0");
+        assert_eq!(
+            "foo@git+ssh://git@github.com/x.git".parse(),
+            Ok(Descriptor::new("foo", "git+ssh://git@github.com/x.git"))
+        );
+        assert_eq!("foo@npm:bar@^1.0.0".parse(), Ok(Descriptor::new("foo", "npm:bar@^1.0.0")));
+        assert_eq!("@scope/foo".parse(), Ok(Descriptor::new("@scope/foo", "")));
+        assert_eq!("".parse::<Descriptor>(), Err(()));
+    }
+
+    #[test]
+    fn groups_locks_into_entries() {
+        let entry = LockEntry {
+            descriptors: vec![Descriptor::new("chalk", "^2.0.0"), Descriptor::new("chalk", "^2.4.1")],
+            version: Some("2.4.2".into()),
+            ..LockEntry::default()
+        };
+        let locks = entry.to_locks();
+        assert_eq!(locks.len(), 2);
+        assert_eq!(LockEntry::from_locks(&locks), vec![entry]);
+    }
+
+    #[test]
+    fn validates_versions_and_urls() {
+        let mut entry = LockEntry { version: Some("2.4".into()), ..LockEntry::default() };
+        match entry.validate() {
+            Err(Error::InvalidVersion { ref value }) => assert_eq!(value, "2.4"),
+            other => panic!("expected an invalid version, got {:?}", other),
+        }
+
+        entry.version = Some("2.4.2".into());
+        entry.resolved = Some("chalk-2.4.2.tgz".into());
+        match entry.validate() {
+            Err(Error::InvalidUrl { .. }) => (),
+            other => panic!("expected an invalid url, got {:?}", other),
+        }
+    }
+}
@@ -1,7 +1,134 @@
-pub use failure::Error;
-use indent_tokenizer;
+//! The error type of this crate.
 
-/// Wraps `ident_tokenizer::Error`
-#[derive(Debug, Fail)]
-#[fail(display = "invalid indentation in line {:?}", _0)]
-pub struct IndentationFail(pub indent_tokenizer::Error);
+use serde_json;
+use serde_yaml;
+use url;
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Where something was found in a lockfile, lines and columns start at `1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Span {
+    pub line:   usize,
+    pub column: usize,
+    /// Length in bytes
+    pub len:    usize,
+}
+
+impl Span {
+    pub fn new(line: usize, column: usize, len: usize) -> Span {
+        Span { line, column, len }
+    }
+
+    /// The whole line, given its content without the line break.
+    pub fn line(line: usize, content: &str) -> Span {
+        Span::new(line, 1, content.len())
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Everything that can go wrong while reading lockfiles and manifests.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing failed, `path` is `None` for readers and stdin.
+    Io { path: Option<PathBuf>, source: io::Error },
+    /// A line is indented by something else than 0, 2 or 4 spaces.
+    Indentation { line: usize },
+    /// A line that does not fit the `yarn.lock` syntax.
+    Syntax { span: Span, expected: &'static str },
+    /// A version that is not valid semver.
+    InvalidVersion { value: String },
+    /// A `resolved` field that is not a valid url.
+    InvalidUrl { value: String, source: url::ParseError },
+    /// A lockfile this crate can't read.
+    UnsupportedFormat(String),
+    /// A `package.json` that is not a JSON object.
+    InvalidManifest,
+    /// Registry metadata without a `name` or `versions`.
+    InvalidPackument,
+    /// No locked or cached version satisfies a descriptor.
+    Unresolved { name: String, range: String },
+    /// A `resolutions` pattern yarn can't use.
+    InvalidResolution { pattern: String, reason: &'static str },
+    /// Removing the `removed` descriptors from a lockfile needs the `package.json`, to keep direct dependencies.
+    ManifestRequired { removed: Vec<String> },
+    Json(serde_json::Error),
+    Yaml(serde_yaml::Error),
+}
+
+impl Error {
+    /// Wraps an `io::Error` that occurred on `path`, for use with `map_err`.
+    pub fn io<P: AsRef<Path>>(path: P) -> impl FnOnce(io::Error) -> Error {
+        let path = path.as_ref().to_owned();
+        move |source| Error::Io { path: Some(path), source }
+    }
+
+    pub(crate) fn syntax(line: usize, expected: &'static str) -> Error {
+        Error::Syntax { span: Span::new(line, 1, 0), expected }
+    }
+
+    /// Moves line numbers down by `lines`, for errors in a part of a lockfile.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn offset_lines(self, lines: usize) -> Error {
+        match self {
+            Error::Indentation { line } => Error::Indentation { line: line + lines },
+            Error::Syntax { span, expected } => Error::Syntax { span: Span { line: span.line + lines, ..span }, expected },
+            error => error,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io { path: Some(ref path), ref source } => write!(f, "{}: {}", path.display(), source),
+            Error::Io { path: None, ref source } => write!(f, "{}", source),
+            Error::Indentation { line } => write!(f, "invalid indentation in line {}", line),
+            Error::Syntax { span, expected } => write!(f, "syntax error at {}: expected {}", span, expected),
+            Error::InvalidVersion { ref value } => write!(f, "invalid version {:?}", value),
+            Error::InvalidUrl { ref value, ref source } => write!(f, "invalid url {:?}: {}", value, source),
+            Error::UnsupportedFormat(ref format) => write!(f, "unsupported lockfile format: {}", format),
+            Error::InvalidManifest => write!(f, "package.json is not an object"),
+            Error::InvalidPackument => write!(f, "registry metadata without a name or versions"),
+            Error::Unresolved { ref name, ref range } => write!(f, "could not resolve {}@{}", name, range),
+            Error::InvalidResolution { ref pattern, reason } => write!(f, "invalid resolution {:?}: {}", pattern, reason),
+            Error::ManifestRequired { ref removed } => {
+                write!(f, "the package.json is needed to tell whether {} can be removed", removed.join(", "))
+            }
+            Error::Json(ref error) => write!(f, "invalid JSON: {}", error),
+            Error::Yaml(ref error) => write!(f, "invalid YAML: {}", error),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io { ref source, .. } => Some(source),
+            Error::InvalidUrl { ref source, .. } => Some(source),
+            Error::Json(ref error) => Some(error),
+            Error::Yaml(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Error {
+        Error::Json(error)
+    }
+}
+
+impl From<serde_yaml::Error> for Error {
+    fn from(error: serde_yaml::Error) -> Error {
+        Error::Yaml(error)
+    }
+}
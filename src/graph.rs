@@ -0,0 +1,356 @@
+//! Relations between the locks of one lockfile.
+//!
+//! Dependencies in a `yarn.lock` are only recorded as name and range,
+//! the `Graph` resolves those back to the locks that satisfy them.
+
+use semver::VersionReq;
+
+use std::cmp;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use super::DependencyLock;
+use npm_semver::parse_reqs;
+
+/// Index over a set of locks, one node per resolved `name@version`.
+#[derive(Debug)]
+pub struct Graph<'a> {
+    by_name: HashMap<&'a str, Vec<&'a DependencyLock>>,
+}
+
+/// Locks that depend on each other in a circle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cycle<'a> {
+    /// The strongly connected component, sorted by name and version
+    pub members: Vec<&'a DependencyLock>,
+    /// A shortest walk from the first member back to itself
+    pub path:    Vec<&'a DependencyLock>,
+}
+
+/// Identifies one resolved package as `name@version`.
+pub fn lock_id(lock: &DependencyLock) -> String {
+    match lock.version {
+        Some(ref version) => format!("{}@{}", lock.name, version),
+        None => lock.name.clone(),
+    }
+}
+
+impl<'a> Graph<'a> {
+    pub fn new(locks: &'a [DependencyLock]) -> Graph<'a> {
+        let mut by_name: HashMap<&'a str, Vec<&'a DependencyLock>> = HashMap::new();
+        for lock in locks {
+            let versions = by_name.entry(&lock.name).or_default();
+            // several headlines of one block produce equal locks
+            if !versions.iter().any(|known| known.version == lock.version) {
+                versions.push(lock);
+            }
+        }
+        for versions in by_name.values_mut() {
+            versions.sort_by(|a, b| a.version.cmp(&b.version));
+        }
+        Graph { by_name }
+    }
+
+    /// All resolved versions of a package, lowest first.
+    pub fn versions(&self, name: &str) -> &[&'a DependencyLock] {
+        self.by_name.get(name).map(|v| v.as_slice()).unwrap_or(&[])
+    }
+
+    /// Every node of the graph, sorted by name and version.
+    pub fn nodes(&self) -> Vec<&'a DependencyLock> {
+        let mut names = self.by_name.keys().collect::<Vec<_>>();
+        names.sort();
+        names.into_iter()
+            .flat_map(|name| self.by_name[name].iter().cloned())
+            .collect()
+    }
+
+    /// Finds the highest locked version of `name` that satisfies `reqs`.
+    ///
+    /// Requirements yarn could not express as semver (git urls, tags, ...) fall back to the only
+    /// locked version, if there is exactly one.
+    pub fn resolve(&self, name: &str, reqs: &[VersionReq]) -> Option<&'a DependencyLock> {
+        let versions = self.versions(name);
+        versions
+            .iter()
+            .rev()
+            .find(|lock| lock.satisfies(reqs))
+            .or_else(|| if versions.len() == 1 { versions.first() } else { None })
+            .cloned()
+    }
+
+    /// Like `resolve`, but takes the range as written in a `package.json`.
+    ///
+    /// Dist-tags, urls and other ranges that are not semver only resolve if one version is locked.
+    pub fn resolve_range(&self, name: &str, range: &str) -> Option<&'a DependencyLock> {
+        match parse_reqs(range) {
+            Some(reqs) => self.resolve(name, &reqs),
+            None => match self.versions(name) {
+                [only] => Some(*only),
+                _ => None,
+            },
+        }
+    }
+
+    /// The resolved dependencies of one lock, required and optional ones, sorted by name.
+    pub fn dependencies_of(&self, lock: &DependencyLock) -> Vec<&'a DependencyLock> {
+        self.edges_of(lock).into_iter().map(|(dependency, _)| dependency).collect()
+    }
+
+    /// Like `dependencies_of`, paired with whether the dependency is optional.
+    pub fn edges_of(&self, lock: &DependencyLock) -> Vec<(&'a DependencyLock, bool)> {
+        let mut dependencies = lock.optional_dependencies
+            .iter()
+            .map(|(name, reqs)| (name, (reqs, true)))
+            .collect::<BTreeMap<_, _>>();
+        // a dependency that is listed in both places is required
+        dependencies.extend(lock.dependencies.iter().map(|(name, reqs)| (name, (reqs, false))));
+        dependencies
+            .into_iter()
+            .filter_map(|(name, (reqs, optional))| {
+                let resolved = self.resolve(name, reqs);
+                if resolved.is_none() {
+                    warn!("{} depends on {}, which is not locked", lock_id(lock), name);
+                }
+                resolved.map(|resolved| (resolved, optional))
+            })
+            .collect()
+    }
+
+    /// The locks that depend on `lock`, required or optionally, sorted by name and version.
+    pub fn dependents(&self, lock: &DependencyLock) -> Vec<&'a DependencyLock> {
+        self.nodes()
+            .into_iter()
+            .filter(|dependent| {
+                dependent.dependencies
+                    .get(&lock.name)
+                    .or_else(|| dependent.optional_dependencies.get(&lock.name))
+                    .and_then(|reqs| self.resolve(&lock.name, reqs))
+                    .map(|resolved| resolved.version == lock.version)
+                    .unwrap_or(false)
+            })
+            .collect()
+    }
+
+    /// Packages that are locked in more than one version, sorted by name.
+    pub fn duplicates(&self) -> Vec<(&'a str, &[&'a DependencyLock])> {
+        let mut duplicates = self.by_name
+            .iter()
+            .filter(|&(_, versions)| versions.len() > 1)
+            .map(|(name, versions)| (*name, versions.as_slice()))
+            .collect::<Vec<_>>();
+        duplicates.sort_by_key(|&(name, _)| name);
+        duplicates
+    }
+
+    /// Ids of all locks reachable from the given `package.json` style requirements.
+    pub fn reachable<'r, I>(&self, roots: I) -> HashSet<String>
+    where
+        I: IntoIterator<Item = (&'r String, &'r String)>,
+    {
+        self.walk(roots, true)
+    }
+
+    /// Like `reachable`, but without following optional dependencies.
+    pub fn required_reachable<'r, I>(&self, roots: I) -> HashSet<String>
+    where
+        I: IntoIterator<Item = (&'r String, &'r String)>,
+    {
+        self.walk(roots, false)
+    }
+
+    fn walk<'r, I>(&self, roots: I, optional: bool) -> HashSet<String>
+    where
+        I: IntoIterator<Item = (&'r String, &'r String)>,
+    {
+        let mut seen = HashSet::new();
+        let mut queue = roots
+            .into_iter()
+            .filter_map(|(name, range)| self.resolve_range(name, range))
+            .collect::<VecDeque<_>>();
+
+        while let Some(lock) = queue.pop_front() {
+            if seen.insert(lock_id(lock)) {
+                queue.extend(
+                    self.edges_of(lock)
+                        .into_iter()
+                        .filter(|&(_, is_optional)| optional || !is_optional)
+                        .map(|(dependency, _)| dependency),
+                );
+            }
+        }
+        seen
+    }
+
+    /// Groups the nodes into strongly connected components (Tarjan), each sorted by name and version.
+    ///
+    /// Every node ends up in exactly one component, most of them on its own.
+    pub fn strongly_connected(&self) -> Vec<Vec<&'a DependencyLock>> {
+        let nodes = self.nodes();
+        let ids = nodes.iter().enumerate().map(|(i, lock)| (lock_id(lock), i)).collect::<HashMap<_, _>>();
+        let edges = nodes
+            .iter()
+            .map(|lock| self.dependencies_of(lock).iter().map(|dependency| ids[&lock_id(dependency)]).collect())
+            .collect::<Vec<Vec<usize>>>();
+
+        let mut index = vec![None; nodes.len()];
+        let mut lowlink = vec![0; nodes.len()];
+        let mut on_stack = vec![false; nodes.len()];
+        let mut stack = Vec::new();
+        let mut counter = 0;
+        let mut components = Vec::new();
+
+        for start in 0..nodes.len() {
+            if index[start].is_some() {
+                continue;
+            }
+            // explicit call stack of (node, next edge), lockfiles can be deeper than the thread's stack
+            let mut calls = vec![(start, 0)];
+            while let Some((node, edge)) = calls.pop() {
+                if edge == 0 {
+                    index[node] = Some(counter);
+                    lowlink[node] = counter;
+                    counter += 1;
+                    stack.push(node);
+                    on_stack[node] = true;
+                }
+                if let Some(&next) = edges[node].get(edge) {
+                    calls.push((node, edge + 1));
+                    match index[next] {
+                        None => calls.push((next, 0)),
+                        Some(next_index) if on_stack[next] => lowlink[node] = cmp::min(lowlink[node], next_index),
+                        Some(_) => (),
+                    }
+                    continue;
+                }
+
+                if Some(lowlink[node]) == index[node] {
+                    let mut component = Vec::new();
+                    while let Some(member) = stack.pop() {
+                        on_stack[member] = false;
+                        component.push(member);
+                        if member == node {
+                            break;
+                        }
+                    }
+                    component.sort();
+                    components.push(component.into_iter().map(|member| nodes[member]).collect::<Vec<_>>());
+                }
+                if let Some(&(parent, _)) = calls.last() {
+                    lowlink[parent] = cmp::min(lowlink[parent], lowlink[node]);
+                }
+            }
+        }
+        components.sort_by(|a, b| (a[0].name.as_str(), &a[0].version).cmp(&(b[0].name.as_str(), &b[0].version)));
+        components
+    }
+
+    /// The dependency cycles, one per strongly connected component that has any.
+    pub fn cycles(&self) -> Vec<Cycle<'a>> {
+        self.strongly_connected()
+            .into_iter()
+            .filter_map(|members| {
+                let path = self.cycle_path(&members)?;
+                Some(Cycle { members, path })
+            })
+            .collect()
+    }
+
+    /// Shortest walk from the first member back to it, staying inside the component.
+    fn cycle_path(&self, members: &[&'a DependencyLock]) -> Option<Vec<&'a DependencyLock>> {
+        let inside = members.iter().map(|lock| lock_id(lock)).collect::<HashSet<_>>();
+        let start = members[0];
+        let start_id = lock_id(start);
+
+        let mut previous: HashMap<String, &'a DependencyLock> = HashMap::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(start);
+        while let Some(lock) = queue.pop_front() {
+            for dependency in self.dependencies_of(lock) {
+                let id = lock_id(dependency);
+                if !inside.contains(&id) || previous.contains_key(&id) {
+                    continue;
+                }
+                previous.insert(id.clone(), lock);
+                if id == start_id {
+                    let mut path = vec![start];
+                    let mut current = lock;
+                    while lock_id(current) != start_id {
+                        path.push(current);
+                        current = previous[&lock_id(current)];
+                    }
+                    path.push(start);
+                    path.reverse();
+                    return Some(path);
+                }
+                queue.push_back(dependency);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse;
+
+    #[test]
+    fn finds_cycles() {
+        let locks = parse(r#"
+babel-runtime@^6.0.0:
+  version "6.26.0"
+  dependencies:
+    core-js "^2.4.0"
+
+core-js@^2.4.0:
+  version "2.6.11"
+  dependencies:
+    core-js-compat "^2.0.0"
+
+core-js-compat@^2.0.0:
+  version "2.0.0"
+  dependencies:
+    babel-runtime "^6.0.0"
+    core-js "^2.4.0"
+
+left-pad@^1.0.0:
+  version "1.3.0"
+  dependencies:
+    babel-runtime "^6.0.0"
+"#).unwrap();
+        let graph = Graph::new(&locks);
+
+        let components = graph.strongly_connected();
+        assert_eq!(components.len(), 2);
+
+        let cycles = graph.cycles();
+        assert_eq!(cycles.len(), 1);
+        let ids = |locks: &[&DependencyLock]| locks.iter().map(|lock| lock_id(lock)).collect::<Vec<_>>();
+        assert_eq!(ids(&cycles[0].members), vec!["babel-runtime@6.26.0", "core-js@2.6.11", "core-js-compat@2.0.0"]);
+        assert_eq!(
+            ids(&cycles[0].path),
+            vec!["babel-runtime@6.26.0", "core-js@2.6.11", "core-js-compat@2.0.0", "babel-runtime@6.26.0"]
+        );
+    }
+
+    #[test]
+    fn finds_cycles_through_optional_dependencies() {
+        let locks = parse(r#"
+a@^1.0.0:
+  version "1.0.0"
+  optionalDependencies:
+    b "^1.0.0"
+
+b@^1.0.0:
+  version "1.0.0"
+  dependencies:
+    a "^1.0.0"
+"#).unwrap();
+        let graph = Graph::new(&locks);
+
+        let cycles = graph.cycles();
+        assert_eq!(cycles.len(), 1);
+        let ids = cycles[0].path.iter().map(|lock| lock_id(lock)).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["a@1.0.0", "b@1.0.0", "a@1.0.0"]);
+    }
+}
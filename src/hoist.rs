@@ -0,0 +1,297 @@
+//! Computes a flat `node_modules` layout from the locks.
+//!
+//! Every package is placed as close to the root as possible,
+//! nested copies are only created where another version already occupies the spot.
+
+use semver::{Version, VersionReq};
+
+use std::collections::{BTreeMap, VecDeque};
+
+use super::DependencyLock;
+use graph::{lock_id, Graph};
+use manifest::{glob_matches, Manifest, Workspace};
+use npm_semver::parse_reqs;
+
+/// Maps install locations like `node_modules/a/node_modules/b` to the lock installed there.
+#[derive(Debug, Default)]
+pub struct Layout<'a> {
+    pub packages: BTreeMap<String, &'a DependencyLock>,
+    /// Symlinks to the workspaces, like `node_modules/a` to `packages/a`
+    pub links:    BTreeMap<String, String>,
+}
+
+/// Where `name` is installed for a package living at `location`.
+pub fn slot(location: &str, name: &str) -> String {
+    if location.is_empty() {
+        format!("node_modules/{}", name)
+    } else {
+        format!("{}/node_modules/{}", location, name)
+    }
+}
+
+/// The location of the package whose `node_modules` contains `location`.
+pub fn parent_location(location: &str) -> Option<&str> {
+    if location.is_empty() {
+        None
+    } else {
+        Some(location.rfind("/node_modules/").map(|i| &location[..i]).unwrap_or(""))
+    }
+}
+
+fn is_within(location: &str, folder: &str) -> bool {
+    folder.is_empty() || location == folder || location.starts_with(&format!("{}/", folder))
+}
+
+/// A dependency waiting to be placed.
+struct Pending<'a> {
+    parent:    String,
+    lock:      &'a DependencyLock,
+    /// Package names from the workspace down to the lock, matched against `nohoist`
+    path:      Vec<String>,
+    /// Location of the workspace the path starts in, empty for the root
+    workspace: String,
+}
+
+impl<'a> Layout<'a> {
+    /// Places `lock` for a dependent at `parent`, returns the new location unless it was already there.
+    ///
+    /// The lock is not hoisted above `ceiling`.
+    fn place(&mut self, parent: &str, lock: &'a DependencyLock, ceiling: &str) -> Option<String> {
+        let mut candidate = None;
+        let mut current = Some(parent);
+
+        while let Some(location) = current {
+            let target = slot(location, &lock.name);
+            match self.packages.get(&target) {
+                Some(existing) if existing.version == lock.version => return None,
+                Some(_) => break,
+                None if self.links.contains_key(&target) => break,
+                None => candidate = Some(location),
+            }
+            if location == ceiling {
+                break;
+            }
+            current = parent_location(location);
+        }
+
+        match candidate {
+            Some(location) => {
+                let target = slot(location, &lock.name);
+                self.packages.insert(target.clone(), lock);
+                Some(target)
+            }
+            None => {
+                warn!("can't place {} below {:?}", lock_id(lock), parent);
+                None
+            }
+        }
+    }
+
+    /// Resolves the requirements of the given `package.json`, including dev and optional ones.
+    pub fn hoist(graph: &Graph<'a>, manifest: &Manifest) -> Layout<'a> {
+        Layout::hoist_workspaces(graph, manifest, &[])
+    }
+
+    /// Lays out the root `package.json` and its workspaces the way yarn v1 does.
+    ///
+    /// Workspaces are linked into the root `node_modules` and share its hoisted packages. Packages whose
+    /// path matches a `nohoist` glob stay in the `node_modules` of their workspace.
+    pub fn hoist_workspaces(graph: &Graph<'a>, root: &Manifest, workspaces: &[Workspace]) -> Layout<'a> {
+        let mut layout = Layout::default();
+        let mut nohoist = root.nohoist.clone();
+        let mut names = BTreeMap::new();
+
+        for workspace in workspaces {
+            let name = workspace.manifest.name.clone().unwrap_or_else(|| {
+                workspace.location.rsplit('/').next().unwrap_or_default().to_string()
+            });
+            layout.links.insert(slot("", &name), workspace.location.clone());
+            // nohoist globs of a workspace are relative to it
+            nohoist.extend(workspace.manifest.nohoist.iter().map(|pattern| format!("{}/{}", name, pattern)));
+            names.insert(name, workspace);
+        }
+        // `workspace:` ranges and ranges the workspace satisfies are linked instead of installed
+        let linked = |name: &str, range: &str| {
+            names.get(name).is_some_and(|workspace| {
+                if range.starts_with("workspace:") {
+                    return true;
+                }
+                let version = workspace.manifest.version.as_ref().and_then(|version| Version::parse(version).ok());
+                match (version, parse_reqs(range)) {
+                    (Some(version), Some(reqs)) => reqs.iter().any(|req| req.matches(&version)),
+                    (None, Some(reqs)) => reqs.contains(&VersionReq::any()),
+                    // tags and urls are fetched like any other package
+                    (_, None) => false,
+                }
+            })
+        };
+
+        let projects = Some((String::new(), None, root))
+            .into_iter()
+            .chain(names.iter().map(|(name, workspace)| {
+                (workspace.location.clone(), Some(name.clone()), &workspace.manifest)
+            }));
+        let mut queue = VecDeque::new();
+        for (location, name, manifest) in projects {
+            for (dependency, range) in manifest
+                .dependencies
+                .iter()
+                .chain(&manifest.dev_dependencies)
+                .chain(&manifest.optional_dependencies)
+            {
+                if linked(dependency, range) {
+                    continue;
+                }
+                if let Some(lock) = graph.resolve_range(dependency, range) {
+                    queue.push_back(Pending {
+                        parent: location.clone(),
+                        lock,
+                        path: name.iter().cloned().chain(Some(dependency.clone())).collect(),
+                        workspace: location.clone(),
+                    });
+                }
+            }
+        }
+
+        // breadth first, so shallow dependents claim the top level spots
+        while let Some(pending) = queue.pop_front() {
+            let path = pending.path.join("/");
+            let ceiling = if !nohoist.iter().any(|pattern| glob_matches(pattern, &path)) {
+                ""
+            } else if is_within(&pending.parent, &pending.workspace) {
+                pending.workspace.as_str()
+            } else {
+                // the dependent was hoisted out of the workspace, so stay next to it
+                pending.parent.as_str()
+            };
+            if let Some(location) = layout.place(&pending.parent, pending.lock, ceiling) {
+                for dependency in graph.dependencies_of(pending.lock) {
+                    queue.push_back(Pending {
+                        parent:    location.clone(),
+                        lock:      dependency,
+                        path:      pending.path.iter().cloned().chain(Some(dependency.name.clone())).collect(),
+                        workspace: pending.workspace.clone(),
+                    });
+                }
+            }
+        }
+
+        layout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse;
+
+    #[test]
+    fn finds_parent_locations() {
+        assert_eq!(parent_location(""), None);
+        assert_eq!(parent_location("node_modules/a"), Some(""));
+        assert_eq!(parent_location("node_modules/@s/a/node_modules/b"), Some("node_modules/@s/a"));
+        assert_eq!(slot("node_modules/@s/a", "b"), "node_modules/@s/a/node_modules/b");
+        assert_eq!(parent_location("packages/a/node_modules/b"), Some("packages/a"));
+        assert_eq!(parent_location("packages/a"), Some(""));
+    }
+
+    #[test]
+    fn hoists_workspaces() {
+        let locks = parse(r#"
+a@^1.0.0:
+  version "1.0.0"
+  dependencies:
+    c "^1.0.0"
+
+b@^1.0.0:
+  version "1.0.0"
+  dependencies:
+    c "^2.0.0"
+
+c@^1.0.0:
+  version "1.1.0"
+
+c@^2.0.0:
+  version "2.0.0"
+
+react-native@^0.60.0:
+  version "0.60.0"
+  dependencies:
+    c "^1.0.0"
+"#).unwrap();
+        let graph = Graph::new(&locks);
+        let root = Manifest::parse(r#"{
+            "private": true,
+            "dependencies": { "a": "^1.0.0" },
+            "workspaces": { "packages": ["packages/*"], "nohoist": ["**/react-native"] }
+        }"#).unwrap();
+        let workspace = |location: &str, manifest: &str| Workspace {
+            location: location.to_string(),
+            manifest: Manifest::parse(manifest).unwrap(),
+        };
+        let workspaces = vec![
+            workspace("packages/one", r#"{ "name": "one", "version": "1.0.0", "dependencies": { "b": "^1.0.0" } }"#),
+            workspace("packages/two", r#"{
+                "name": "two",
+                "dependencies": { "one": "^1.0.0", "react-native": "^0.60.0" }
+            }"#),
+        ];
+
+        let layout = Layout::hoist_workspaces(&graph, &root, &workspaces);
+        let packages = layout.packages
+            .iter()
+            .map(|(location, lock)| (location.as_str(), lock_id(lock)))
+            .collect::<Vec<_>>();
+        assert_eq!(packages, vec![
+            ("node_modules/a", "a@1.0.0".to_string()),
+            ("node_modules/b", "b@1.0.0".to_string()),
+            ("node_modules/b/node_modules/c", "c@2.0.0".to_string()),
+            ("node_modules/c", "c@1.1.0".to_string()),
+            ("packages/two/node_modules/react-native", "react-native@0.60.0".to_string()),
+        ]);
+        assert_eq!(layout.links.get("node_modules/one"), Some(&"packages/one".to_string()));
+        assert_eq!(layout.links.len(), 2);
+    }
+
+    #[test]
+    fn links_matching_ranges_and_installs_optional_dependencies() {
+        let locks = parse(r#"
+one@latest:
+  version "2.0.0"
+
+b@^1.0.0:
+  version "1.0.0"
+  optionalDependencies:
+    fsevents "^1.2.0"
+
+fsevents@^1.2.0:
+  version "1.2.7"
+"#).unwrap();
+        let graph = Graph::new(&locks);
+        let root = Manifest::parse(r#"{
+            "private": true,
+            "dependencies": { "b": "^1.0.0" },
+            "workspaces": ["packages/*"]
+        }"#).unwrap();
+        let workspace = |location: &str, manifest: &str| Workspace {
+            location: location.to_string(),
+            manifest: Manifest::parse(manifest).unwrap(),
+        };
+        let workspaces = vec![
+            workspace("packages/app", r#"{ "name": "app", "dependencies": { "one": "latest", "two": "*" } }"#),
+            workspace("packages/one", r#"{ "name": "one", "version": "1.0.0" }"#),
+            workspace("packages/two", r#"{ "name": "two" }"#),
+        ];
+
+        let layout = Layout::hoist_workspaces(&graph, &root, &workspaces);
+        let packages = layout.packages
+            .iter()
+            .map(|(location, lock)| (location.as_str(), lock_id(lock)))
+            .collect::<Vec<_>>();
+        assert_eq!(packages, vec![
+            ("node_modules/b", "b@1.0.0".to_string()),
+            ("node_modules/fsevents", "fsevents@1.2.7".to_string()),
+            ("packages/app/node_modules/one", "one@2.0.0".to_string()),
+        ]);
+    }
+}
@@ -0,0 +1,360 @@
+//! Compares an installed `node_modules` folder with the locks.
+//!
+//! Dependencies are looked up the way node resolves them, walking up the `node_modules` folders of the
+//! dependent, so the check doesn't depend on where the package manager hoisted them.
+
+use semver::{Version, VersionReq};
+use serde_json::{self, Value};
+
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, prelude::*};
+use std::path::Path;
+
+use super::DependencyLock;
+use error::Error;
+use graph::Graph;
+use hoist::{parent_location, slot};
+use manifest::{Manifest, Workspace};
+use npm_semver::{format_reqs, parse_reqs};
+
+/// A package found in a `node_modules` folder.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Installed {
+    pub name:      String,
+    pub version:   Option<String>,
+    /// `_resolved` as written by the package manager
+    pub resolved:  Option<String>,
+    /// `_integrity` as written by the package manager
+    pub integrity: Option<String>,
+}
+
+/// Installed packages by location, like `node_modules/a/node_modules/b`.
+pub type Tree = BTreeMap<String, Installed>;
+
+fn read_package(dir: &Path) -> Result<Installed, Error> {
+    let path = dir.join("package.json");
+    let mut content = String::new();
+    File::open(&path)
+        .and_then(|mut file| file.read_to_string(&mut content))
+        .map_err(Error::io(&path))?;
+    let value: Value = serde_json::from_str(&content)?;
+    let field = |key: &str| value.get(key).and_then(Value::as_str).map(ToString::to_string);
+    Ok(Installed {
+        name:      field("name").unwrap_or_default(),
+        version:   field("version"),
+        resolved:  field("_resolved"),
+        integrity: field("_integrity"),
+    })
+}
+
+/// Reads the packages in the `node_modules` of `location`, and theirs.
+fn read_node_modules(root: &Path, location: &str, tree: &mut Tree) -> Result<(), Error> {
+    let dir = root.join(location).join("node_modules");
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(error) => return Err(Error::io(&dir)(error)),
+    };
+    let mut names = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(Error::io(&dir))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let file_type = entry.file_type().map_err(Error::io(&dir))?;
+        // links are workspaces or `yarn link`ed packages, `.bin` and friends are no packages
+        if name.starts_with('.') || !file_type.is_dir() {
+            continue;
+        }
+        if name.starts_with('@') {
+            let scope = entry.path();
+            for scoped in fs::read_dir(&scope).map_err(Error::io(&scope))? {
+                let scoped = scoped.map_err(Error::io(&scope))?;
+                if scoped.file_type().map(|file_type| file_type.is_dir()).unwrap_or(false) {
+                    names.push(format!("{}/{}", name, scoped.file_name().to_string_lossy()));
+                }
+            }
+        } else {
+            names.push(name);
+        }
+    }
+    names.sort();
+
+    for name in names {
+        let package_location = slot(location, &name);
+        let package_dir = root.join(&package_location);
+        if !package_dir.join("package.json").is_file() {
+            continue;
+        }
+        tree.insert(package_location.clone(), read_package(&package_dir)?);
+        read_node_modules(root, &package_location, tree)?;
+    }
+    Ok(())
+}
+
+/// Reads the `node_modules` of the project in `root` and of its workspaces.
+pub fn read_tree<P: AsRef<Path>>(root: P, workspaces: &[Workspace]) -> Result<Tree, Error> {
+    let root = root.as_ref();
+    let mut tree = Tree::new();
+    read_node_modules(root, "", &mut tree)?;
+    for workspace in workspaces {
+        read_node_modules(root, &workspace.location, &mut tree)?;
+    }
+    Ok(tree)
+}
+
+/// How the installed packages drifted from the lockfile.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// A dependency of the package at `dependent` can't be found, the root is `""`.
+    Missing { dependent: String, name: String, range: String },
+    /// The package a dependency resolves to doesn't satisfy its range.
+    WrongVersion { location: String, name: String, found: String, range: String },
+    /// An installed `name@version` that is not in the lockfile.
+    Unlisted { location: String, name: String, version: String },
+    /// A locked version that was installed from another tarball.
+    OtherSource { location: String, name: String, version: String },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Problem::Missing { ref dependent, ref name, ref range } => {
+                let dependent = if dependent.is_empty() { "the project" } else { dependent.as_str() };
+                write!(f, "{}@{} is missing for {}", name, range, dependent)
+            }
+            Problem::WrongVersion { ref location, ref found, ref range, .. } => {
+                write!(f, "{} is {}, but {} is required", location, found, range)
+            }
+            Problem::Unlisted { ref location, ref version, .. } => {
+                write!(f, "{} is {}, which is not in the lockfile", location, version)
+            }
+            Problem::OtherSource { ref location, ref version, .. } => {
+                write!(f, "{} is {}, but not the locked tarball", location, version)
+            }
+        }
+    }
+}
+
+/// Finds the package node would load for `name` from the package at `dependent`.
+fn find<'t>(tree: &'t Tree, dependent: &str, name: &str) -> Option<(String, &'t Installed)> {
+    let mut current = Some(dependent);
+    while let Some(location) = current {
+        let candidate = slot(location, name);
+        if let Some(installed) = tree.get(&candidate) {
+            return Some((candidate, installed));
+        }
+        current = parent_location(location);
+    }
+    None
+}
+
+fn strip_hash(url: &str) -> &str {
+    url.split('#').next().unwrap_or(url)
+}
+
+/// Whether the installed package came from the tarball of `lock`, as far as it tells.
+fn same_source(installed: &Installed, lock: &DependencyLock) -> bool {
+    if let (Some(installed), Some(locked)) = (installed.integrity.as_ref(), lock.integrity.as_ref()) {
+        return installed == locked;
+    }
+    match (installed.resolved.as_ref(), lock.resolved.as_ref()) {
+        (Some(installed), Some(locked)) => strip_hash(installed) == strip_hash(locked.as_str()),
+        _ => true,
+    }
+}
+
+/// A dependency to look up from the `node_modules` of `dependent`.
+struct Wanted {
+    dependent: String,
+    name:      String,
+    /// The range as written in the manifest or the lock
+    range:     String,
+    /// `None` for ranges like git urls that can't be checked against a version
+    reqs:      Option<Vec<VersionReq>>,
+    optional:  bool,
+}
+
+/// Compares the installed `tree` with the locks, starting at the dependencies of the project and its workspaces.
+pub fn check(graph: &Graph, manifest: &Manifest, workspaces: &[Workspace], tree: &Tree) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    // everything installed, whether anything needs it or not
+    let mut locks = BTreeMap::new();
+    for (location, installed) in tree {
+        let version = installed.version.clone().unwrap_or_default();
+        let parsed = Version::parse(&version).ok();
+        let candidates = graph
+            .versions(&installed.name)
+            .iter()
+            .filter(|lock| lock.version.is_some() && lock.version == parsed)
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            problems.push(Problem::Unlisted { location: location.clone(), name: installed.name.clone(), version });
+        } else if !candidates.iter().any(|lock| same_source(installed, lock)) {
+            problems.push(Problem::OtherSource { location: location.clone(), name: installed.name.clone(), version });
+        } else {
+            locks.insert(location.as_str(), *candidates[0]);
+        }
+    }
+
+    let workspace_names = workspaces.iter().filter_map(|workspace| workspace.manifest.name.as_ref()).collect::<HashSet<_>>();
+    let projects = Some(("", manifest)).into_iter().chain(
+        workspaces.iter().map(|workspace| (workspace.location.as_str(), &workspace.manifest)),
+    );
+    let mut queue = VecDeque::new();
+    for (location, manifest) in projects {
+        let required = manifest.dependencies.iter().chain(&manifest.dev_dependencies).map(|dependency| (dependency, false));
+        let optional = manifest.optional_dependencies.iter().map(|dependency| (dependency, true));
+        for ((name, range), optional) in required.chain(optional) {
+            // workspaces are linked
+            if !workspace_names.contains(name) {
+                queue.push_back(Wanted {
+                    dependent: location.to_string(),
+                    name: name.clone(),
+                    range: range.clone(),
+                    reqs: parse_reqs(range),
+                    optional,
+                });
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    while let Some(Wanted { dependent, name, range, reqs, optional }) = queue.pop_front() {
+        let (location, installed) = match find(tree, &dependent, &name) {
+            Some(found) => found,
+            None => {
+                if !optional {
+                    problems.push(Problem::Missing { dependent, name, range });
+                }
+                continue;
+            }
+        };
+        let version = installed.version.as_ref().and_then(|version| Version::parse(version).ok());
+        let satisfied = match (reqs, version) {
+            (Some(reqs), Some(version)) => reqs.iter().any(|req| req.matches(&version)),
+            // ranges like git urls can't be checked against a version
+            (None, _) => true,
+            (Some(_), None) => false,
+        };
+        if !satisfied {
+            problems.push(Problem::WrongVersion {
+                location: location.clone(),
+                name,
+                found: installed.version.clone().unwrap_or_default(),
+                range,
+            });
+        }
+        if !seen.insert(location.clone()) {
+            continue;
+        }
+        if let Some(lock) = locks.get(location.as_str()) {
+            let required = lock.dependencies.iter().map(|dependency| (dependency, false));
+            let optional = lock.optional_dependencies.iter().map(|dependency| (dependency, true));
+            let mut dependencies = required.chain(optional).collect::<Vec<_>>();
+            dependencies.sort_by_key(|&((name, _), _)| name);
+            for ((dependency, reqs), optional) in dependencies {
+                queue.push_back(Wanted {
+                    dependent: location.clone(),
+                    name: dependency.clone(),
+                    range: format_reqs(reqs),
+                    reqs: Some(reqs.clone()),
+                    optional,
+                });
+            }
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse;
+
+    fn installed(name: &str, version: &str) -> Installed {
+        Installed { name: name.to_string(), version: Some(version.to_string()), resolved: None, integrity: None }
+    }
+
+    #[test]
+    fn finds_drift() {
+        let locks = parse(r#"
+a@^1.0.0:
+  version "1.0.0"
+  integrity sha512-aaa
+  dependencies:
+    b "^1.0.0"
+    c "^1.0.0"
+
+b@^1.0.0:
+  version "1.2.0"
+
+c@^1.0.0:
+  version "1.0.0"
+
+d@^1.0.0:
+  version "1.0.0"
+  integrity sha512-d00
+"#).unwrap();
+        let graph = Graph::new(&locks);
+        let manifest = Manifest::parse(r#"{
+            "dependencies": { "a": "^1.0.0", "d": "^1.0.0" },
+            "optionalDependencies": { "fsevents": "^2.0.0" }
+        }"#).unwrap();
+
+        let mut tree = Tree::new();
+        tree.insert("node_modules/a".into(), Installed { integrity: Some("sha512-aaa".into()), ..installed("a", "1.0.0") });
+        tree.insert("node_modules/b".into(), installed("b", "2.0.0"));
+        tree.insert("node_modules/a/node_modules/c".into(), installed("c", "1.0.0"));
+        tree.insert("node_modules/d".into(), Installed { integrity: Some("sha512-ddd".into()), ..installed("d", "1.0.0") });
+
+        let problems = check(&graph, &manifest, &[], &tree);
+        assert_eq!(problems, vec![
+            Problem::Unlisted { location: "node_modules/b".into(), name: "b".into(), version: "2.0.0".into() },
+            Problem::OtherSource { location: "node_modules/d".into(), name: "d".into(), version: "1.0.0".into() },
+            Problem::WrongVersion {
+                location: "node_modules/b".into(),
+                name:     "b".into(),
+                found:    "2.0.0".into(),
+                range:    "^1.0.0".into(),
+            },
+        ]);
+
+        tree.remove("node_modules/a/node_modules/c");
+        let problems = check(&graph, &manifest, &[], &tree);
+        assert!(problems.contains(&Problem::Missing {
+            dependent: "node_modules/a".into(),
+            name:      "c".into(),
+            range:     "^1.0.0".into(),
+        }));
+    }
+
+    #[test]
+    fn skips_missing_optional_dependencies() {
+        let locks = parse(r#"
+a@^1.0.0:
+  version "1.0.0"
+  optionalDependencies:
+    fsevents "^1.2.0"
+
+fsevents@^1.2.0:
+  version "1.2.7"
+"#).unwrap();
+        let graph = Graph::new(&locks);
+        let manifest = Manifest::parse(r#"{ "dependencies": { "a": "^1.0.0" } }"#).unwrap();
+
+        let mut tree = Tree::new();
+        tree.insert("node_modules/a".into(), installed("a", "1.0.0"));
+        assert_eq!(check(&graph, &manifest, &[], &tree), vec![]);
+
+        tree.insert("node_modules/fsevents".into(), installed("fsevents", "1.1.0"));
+        let problems = check(&graph, &manifest, &[], &tree);
+        assert!(problems.contains(&Problem::WrongVersion {
+            location: "node_modules/fsevents".into(),
+            name:     "fsevents".into(),
+            found:    "1.1.0".into(),
+            range:    "^1.2.0".into(),
+        }));
+    }
+}
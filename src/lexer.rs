@@ -0,0 +1,214 @@
+//! Single pass lexer for the yarn v1 lockfile syntax.
+//!
+//! A `yarn.lock` only has three levels of indentation, so every line can be classified on its own,
+//! without building a tree first.
+
+use std::iter::Enumerate;
+use std::str::Lines;
+
+use error::{Error, Span};
+
+/// One meaningful line of a `yarn.lock`, everything borrowed from the content.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Line<'a> {
+    /// `chalk@^2.0.0, chalk@^2.4.1:`, without the colon
+    Headline(&'a str),
+    /// `  version "2.4.2"`
+    Field(&'a str, &'a str),
+    /// `  dependencies:`, without the colon
+    Section(&'a str),
+    /// `    ansi-styles "^3.2.1"`
+    Entry(&'a str, &'a str),
+}
+
+/// Iterates over the lines of a `yarn.lock` with their line numbers, skipping blanks and comments.
+pub struct Lexer<'a> {
+    lines: Enumerate<Lines<'a>>,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(content: &'a str) -> Lexer<'a> {
+        Lexer { lines: content.lines().enumerate() }
+    }
+}
+
+fn unquote(s: &str) -> &str {
+    if s.len() >= 2 && s.starts_with('"') && s.ends_with('"') {
+        &s[1..s.len() - 1]
+    } else {
+        s
+    }
+}
+
+/// Splits `key value`, where both may be quoted.
+pub fn split_key_value(line: &str) -> Option<(&str, &str)> {
+    let (key, rest) = if let Some(quoted) = line.strip_prefix('"') {
+        let end = quoted.find('"')?;
+        (&quoted[..end], &quoted[end + 1..])
+    } else {
+        let end = line.find(' ').unwrap_or(line.len());
+        (&line[..end], &line[end..])
+    };
+    Some((key, unquote(rest.trim())))
+}
+
+/// Splits `name@range` at the first `@` after the name, like yarn's `normalizePattern`.
+///
+/// Ranges may contain `@` themselves, e.g. `npm:bar@^1.0.0` or `git+ssh://git@github.com/x.git`.
+pub fn split_descriptor(descriptor: &str) -> (&str, &str) {
+    // the leading `@` belongs to the scope
+    let scope = if descriptor.starts_with('@') { 1 } else { 0 };
+    match descriptor[scope..].find('@') {
+        Some(i) => (&descriptor[..scope + i], &descriptor[scope + i + 1..]),
+        None => (descriptor, ""),
+    }
+}
+
+/// Splits a headline into the names and ranges of its comma separated, possibly quoted, descriptors.
+pub fn split_descriptors(headline: &str) -> Vec<(&str, &str)> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, c) in headline.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                parts.push(&headline[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    parts.push(&headline[start..]);
+
+    parts
+        .into_iter()
+        .map(|part| split_descriptor(unquote(part.trim())))
+        .collect()
+}
+
+/// Which map the lines indented by four spaces belong to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Section {
+    None,
+    Dependencies,
+    OptionalDependencies,
+    Other,
+}
+
+impl Section {
+    pub fn named(name: &str) -> Section {
+        match name {
+            "dependencies" => Section::Dependencies,
+            "optionalDependencies" => Section::OptionalDependencies,
+            _ => Section::Other,
+        }
+    }
+}
+
+/// Classifies a single line, `None` for blank lines and comments.
+pub fn classify(number: usize, line: &str) -> Option<Result<Line<'_>, Error>> {
+    let line = line.trim_end();
+    let trimmed = line.trim_start();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return None;
+    }
+
+    let indentation = line.len() - trimmed.len();
+    let unclosed = || Error::Syntax {
+        span:     Span::new(number, indentation + 1, trimmed.len()),
+        expected: "a closing `\"`",
+    };
+    Some(match indentation {
+        0 if trimmed.ends_with(':') => Ok(Line::Headline(&trimmed[..trimmed.len() - 1])),
+        0 => Err(Error::Syntax {
+            span:     Span::new(number, trimmed.len() + 1, 0),
+            expected: "`:` at the end of the headline",
+        }),
+        2 => match split_key_value(trimmed) {
+            Some((key, "")) if key.ends_with(':') => Ok(Line::Section(&key[..key.len() - 1])),
+            Some((key, value)) => Ok(Line::Field(key, value)),
+            None => Err(unclosed()),
+        },
+        4 => match split_key_value(trimmed) {
+            Some((key, value)) => Ok(Line::Entry(key, value)),
+            None => Err(unclosed()),
+        },
+        _ => Err(Error::Indentation { line: number }),
+    })
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<(usize, Line<'a>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for (i, line) in &mut self.lines {
+            if let Some(token) = classify(i + 1, line) {
+                return Some(token.map(|token| (i + 1, token)));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_head_lines() {
+        assert_eq!(
+            split_descriptors(r#""@protobufjs/aspromise@^1.1.1","@protobufjs/aspromise@^1.1.2""#),
+            vec![("@protobufjs/aspromise", "^1.1.1"), ("@protobufjs/aspromise", "^1.1.2")]
+        );
+        assert_eq!(
+            split_descriptors(r#""@ava/babe,-plugin-throws-helper@^2.0.0", "@ava/babel-plugin-throws-helper@^2.0.0""#),
+            vec![("@ava/babe,-plugin-throws-helper", "^2.0.0"), ("@ava/babel-plugin-throws-helper", "^2.0.0")]
+        );
+        assert_eq!(
+            split_descriptors("assertion-error@^1.0.1, assertion-error@^1.0.1"),
+            vec![("assertion-error", "^1.0.1"), ("assertion-error", "^1.0.1")]
+        );
+        assert_eq!(split_descriptors("fstream@>= 0.1.30 < 1"), vec![("fstream", ">= 0.1.30 < 1")]);
+        assert_eq!(
+            split_descriptors(r#""foo@git+ssh://git@github.com/x.git""#),
+            vec![("foo", "git+ssh://git@github.com/x.git")]
+        );
+        assert_eq!(
+            split_descriptors(r#""foo@npm:bar@^1.0.0", "@scope/foo@npm:@scope/bar@^1.0.0""#),
+            vec![("foo", "npm:bar@^1.0.0"), ("@scope/foo", "npm:@scope/bar@^1.0.0")]
+        );
+    }
+
+    #[test]
+    fn splits_key_values() {
+        assert_eq!(split_key_value(r#"version "1.4.0""#), Some(("version", "1.4.0")));
+        assert_eq!(split_key_value("version 3.0.0"), Some(("version", "3.0.0")));
+        assert_eq!(split_key_value(r#""window-size" "0.1.0""#), Some(("window-size", "0.1.0")));
+        assert_eq!(split_key_value(r#"fstream ">= 0.1.30 < 1""#), Some(("fstream", ">= 0.1.30 < 1")));
+        assert_eq!(split_key_value(r#""window-size 0.1.0"#), None);
+    }
+
+    #[test]
+    fn classifies_lines() {
+        let content = "# yarn lockfile v1\n\n\"@babel/highlight@^7.8.3\":\n  version \"7.9.0\"\n  dependencies:\n    js-tokens \"^4.0.0\"\n";
+        let lines = Lexer::new(content).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                (3, Line::Headline("\"@babel/highlight@^7.8.3\"")),
+                (4, Line::Field("version", "7.9.0")),
+                (5, Line::Section("dependencies")),
+                (6, Line::Entry("js-tokens", "^4.0.0")),
+            ]
+        );
+        match Lexer::new("a@^1.0.0:\n   version \"1.0.0\"\n").nth(1) {
+            Some(Err(Error::Indentation { line: 2 })) => (),
+            other => panic!("expected an indentation error, got {:?}", other),
+        }
+        match Lexer::new("a@^1.0.0\n").next() {
+            Some(Err(Error::Syntax { span, .. })) => assert_eq!(span, Span::new(1, 9, 0)),
+            other => panic!("expected a syntax error, got {:?}", other),
+        }
+    }
+}
@@ -1,16 +1,26 @@
 #![allow(dead_code)]
-#[macro_use]
-extern crate failure;
-extern crate indent_tokenizer;
+extern crate flate2;
 #[macro_use]
 extern crate log;
 extern crate multimap;
 #[macro_use]
 extern crate nom;
+#[cfg(test)]
 #[macro_use]
 extern crate pretty_assertions;
+#[cfg(feature = "rayon")]
+extern crate rayon;
 extern crate semver;
 extern crate semver_parser;
+#[cfg(feature = "with-serde")]
+extern crate serde;
+#[cfg(feature = "with-serde")]
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
+extern crate serde_yaml;
+extern crate tar;
 extern crate url;
 
 use multimap::MultiMap;
@@ -22,50 +32,141 @@ use std::ffi::OsStr;
 use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::result::Result;
 
+mod lexer;
 mod parser;
-pub use parser::{parse, parse_by_name};
+pub use parser::{parse, parse_by_name, parse_entries};
+#[cfg(feature = "rayon")]
+pub use parser::{parse_entries_parallel, parse_parallel};
 pub mod error;
+pub use error::Error;
+pub mod entry;
+pub use entry::{Descriptor, LockEntry};
+pub mod stream;
+pub use stream::EntryReader;
 
 pub mod npm_semver;
+pub mod borrowed;
+pub use borrowed::Lockfile;
+pub mod manifest;
+pub mod tarball;
+pub mod graph;
+pub mod hoist;
+pub mod installed;
+pub mod package_lock;
+pub mod pnpm;
+pub mod writer;
+pub mod berry;
+pub mod render;
+pub mod diff;
+pub mod lint;
+pub mod registry;
+pub mod resolve;
+pub mod resolutions;
+#[cfg(feature = "with-serde")]
+pub mod serde_support;
 
 /// Represents one dependency Lock.
 ///
-/// One block in your `yarn.lock` be result in multiple `DependencyLock`s.
-#[derive(Debug)]
+/// One block in your `yarn.lock` be result in multiple `DependencyLock`s, see `LockEntry` for the block itself.
+///
+/// With the `with-serde` feature it (de)serializes into the schema described in `serde_support`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
 pub struct DependencyLock {
     pub name:         String,
+    #[cfg_attr(feature = "with-serde", serde(rename = "range", default, with = "serde_support::opt_req"))]
     pub last_seen:    Option<VersionReq>,
+    #[cfg_attr(feature = "with-serde", serde(default, with = "serde_support::opt_version"))]
     pub version:      Option<Version>,
+    #[cfg_attr(feature = "with-serde", serde(default, with = "serde_support::opt_url"))]
     pub resolved:     Option<Url>,
+    pub integrity:    Option<String>,
+    #[cfg_attr(feature = "with-serde", serde(default, with = "serde_support::dependencies"))]
     pub dependencies: HashMap<String, Vec<VersionReq>>,
+    /// Empty for lockfiles that don't keep them apart from `dependencies`
+    #[cfg_attr(
+        feature = "with-serde",
+        serde(rename = "optionalDependencies", default, with = "serde_support::dependencies")
+    )]
+    pub optional_dependencies: HashMap<String, Vec<VersionReq>>,
 }
 
-fn read_file(path: &Path) -> Result<String, error::Error> {
-    let mut file = if path.is_dir() {
-        let file_path = path.join("yarn.lock");
-        debug!("opening {:?}", file_path);
-        File::open(file_path)?
-    } else {
-        debug!("opening {:?}", path);
-        File::open(path)?
-    };
+impl DependencyLock {
+    /// Whether the locked version matches any of the given requirements.
+    pub fn satisfies(&self, reqs: &[VersionReq]) -> bool {
+        self.version
+            .as_ref()
+            .map(|version| reqs.iter().any(|req| req.matches(version)))
+            .unwrap_or(false)
+    }
+}
+
+/// Reads `path`, or the file called `default_name` inside of it if `path` is a folder.
+fn read_file(path: &Path, default_name: &str) -> Result<String, error::Error> {
+    let path = if path.is_dir() { path.join(default_name) } else { path.to_owned() };
+    debug!("opening {:?}", path);
     let mut content = String::new();
-    file.read_to_string(&mut content).unwrap();
+    File::open(&path)
+        .and_then(|mut file| file.read_to_string(&mut content))
+        .map_err(error::Error::io(&path))?;
     Ok(content)
 }
 
-/// Opens a given file or the `yarn.lock` if a folder is given.
+/// The lockfile formats `open` understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Yarn,
+    Npm,
+    Pnpm,
+}
+
+impl Format {
+    /// The name of the lockfile in a project folder.
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Format::Yarn => "yarn.lock",
+            Format::Npm => "package-lock.json",
+            Format::Pnpm => "pnpm-lock.yaml",
+        }
+    }
+
+    /// Guesses the format from a file name, or looks for a known lockfile in a folder.
+    ///
+    /// Folders without any lockfile fall back to `yarn.lock`.
+    pub fn detect(path: &Path) -> (PathBuf, Format) {
+        let formats = [Format::Yarn, Format::Pnpm, Format::Npm];
+        if path.is_dir() {
+            let found = formats.iter().cloned().find(|format| path.join(format.file_name()).is_file());
+            let format = found.unwrap_or(Format::Yarn);
+            return (path.join(format.file_name()), format);
+        }
+
+        let file_name = path.file_name().and_then(OsStr::to_str).unwrap_or_default();
+        let format = match file_name {
+            "pnpm-lock.yaml" => Format::Pnpm,
+            "package-lock.json" | "npm-shrinkwrap.json" => Format::Npm,
+            _ => Format::Yarn,
+        };
+        (path.to_owned(), format)
+    }
+}
+
+/// Opens a given lockfile, or the `yarn.lock`, `pnpm-lock.yaml` or `package-lock.json` if a folder is given.
 pub fn open<P: AsRef<OsStr> + Sized>(given_path: P) -> Result<Vec<DependencyLock>, error::Error> {
-    let path = Path::new(&given_path);
-    read_file(&path).and_then(|s| parse(&s))
+    let (path, format) = Format::detect(Path::new(&given_path));
+    let content = read_file(&path, format.file_name())?;
+    match format {
+        Format::Yarn => parse(&content),
+        Format::Npm => package_lock::parse_package_lock(&content),
+        Format::Pnpm => pnpm::parse_pnpm_lock(&content).map(|lock| lock.locks),
+    }
 }
 
 pub fn open_by_name<P: AsRef<OsStr> + Sized>(given_path: P) -> Result<MultiMap<String, DependencyLock>, error::Error> {
-    let path = Path::new(&given_path);
-    read_file(&path).and_then(|s| parse_by_name(&s))
+    open(given_path).map(|locks| locks.into_iter().map(|lock| (lock.name.clone(), lock)).collect())
 }
 
 impl fmt::Display for DependencyLock {
@@ -73,7 +174,7 @@ impl fmt::Display for DependencyLock {
         let version = self.version
             .as_ref()
             .map(ToString::to_string)
-            .unwrap_or_else(String::new);
+            .unwrap_or_default();
 
         let last_seen = self.last_seen
             .iter()
@@ -0,0 +1,370 @@
+//! Policy checks on a `yarn.lock`, e.g. for CI.
+//!
+//! Every `Lint` carries the `Span` of the offending headline, descriptor or value,
+//! so it can be reported as an annotation on the lockfile.
+
+use url::Url;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use error::{Error, Span};
+use lexer::{split_descriptors, Lexer, Line};
+
+/// A check that can be turned on and off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Rule {
+    /// `resolved` must not use plain `http`.
+    Https,
+    /// `resolved` must point to one of `Config::allowed_hosts`.
+    AllowedHosts,
+    /// `integrity` must be present and use `sha512`.
+    Integrity,
+    /// No dependencies on git repositories or tarball urls.
+    NoGitOrTarball,
+    /// No package locked in more than `Config::max_versions` versions.
+    MaxVersions,
+    /// Blocks are sorted the way yarn writes them.
+    Sorted,
+    /// `file:`, `link:` and `portal:` descriptors must stay inside the workspace.
+    FileInWorkspace,
+}
+
+impl Rule {
+    pub fn all() -> [Rule; 7] {
+        [
+            Rule::Https,
+            Rule::AllowedHosts,
+            Rule::Integrity,
+            Rule::NoGitOrTarball,
+            Rule::MaxVersions,
+            Rule::Sorted,
+            Rule::FileInWorkspace,
+        ]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::Https => "https",
+            Rule::AllowedHosts => "allowed-hosts",
+            Rule::Integrity => "integrity",
+            Rule::NoGitOrTarball => "no-git-or-tarball",
+            Rule::MaxVersions => "max-versions",
+            Rule::Sorted => "sorted",
+            Rule::FileInWorkspace => "file-in-workspace",
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Rule, String> {
+        Rule::all()
+            .iter()
+            .cloned()
+            .find(|rule| rule.name() == s)
+            .ok_or_else(|| format!("unknown rule {:?}", s))
+    }
+}
+
+/// Which rules to run and their settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub rules:         BTreeSet<Rule>,
+    pub allowed_hosts: Vec<String>,
+    /// Used by `Rule::MaxVersions`.
+    pub max_versions:  usize,
+    /// Absolute path of the workspace, absolute `file:` paths are only allowed inside of it.
+    pub workspace:     Option<PathBuf>,
+}
+
+impl Default for Config {
+    /// `https`, `integrity`, `sorted` and `file-in-workspace`, with the yarn and npm registries allowed.
+    fn default() -> Config {
+        Config {
+            rules:         [Rule::Https, Rule::Integrity, Rule::Sorted, Rule::FileInWorkspace]
+                .iter()
+                .cloned()
+                .collect(),
+            allowed_hosts: vec!["registry.yarnpkg.com".into(), "registry.npmjs.org".into()],
+            max_versions:  1,
+            workspace:     None,
+        }
+    }
+}
+
+impl Config {
+    /// The default settings with every rule turned on.
+    pub fn all() -> Config {
+        Config { rules: Rule::all().iter().cloned().collect(), ..Config::default() }
+    }
+}
+
+/// One violation of a rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lint {
+    pub rule:    Rule,
+    pub span:    Span,
+    pub message: String,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} [{}]", self.span, self.message, self.rule)
+    }
+}
+
+/// A block with the positions the rules need.
+struct Block<'a> {
+    line:        usize,
+    headline:    &'a str,
+    descriptors: Vec<(&'a str, &'a str)>,
+    version:     Option<&'a str>,
+    /// Values with their line
+    resolved:    Option<(usize, &'a str)>,
+    integrity:   Option<(usize, &'a str)>,
+}
+
+/// The span of `part`, which must be a slice of `content` on line `line`.
+fn span_of(content: &str, part: &str, line: usize) -> Span {
+    let offset = part.as_ptr() as usize - content.as_ptr() as usize;
+    let line_start = content[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    Span::new(line, offset - line_start + 1, part.len())
+}
+
+fn read_blocks(content: &str) -> Result<Vec<Block<'_>>, Error> {
+    let mut blocks: Vec<Block> = Vec::new();
+    for line in Lexer::new(content) {
+        let (number, line) = line?;
+        match line {
+            Line::Headline(headline) => blocks.push(Block {
+                line: number,
+                headline,
+                descriptors: split_descriptors(headline),
+                version: None,
+                resolved: None,
+                integrity: None,
+            }),
+            Line::Field(key, value) => {
+                let block = blocks.last_mut().ok_or_else(|| Error::syntax(number, "a headline before the first field"))?;
+                match key {
+                    "version" => block.version = Some(value),
+                    "resolved" => block.resolved = Some((number, value)),
+                    "integrity" => block.integrity = Some((number, value)),
+                    _ => (),
+                }
+            }
+            Line::Section(_) | Line::Entry(..) => (),
+        }
+    }
+    Ok(blocks)
+}
+
+fn is_git_or_tarball(range: &str) -> bool {
+    let prefixes = ["git:", "git+", "github:", "gitlab:", "bitbucket:", "gist:", "http:", "https:"];
+    let suffixes = [".git", ".tgz", ".tar.gz"];
+    // `user/repo` and `user/repo#ref` are shorthands for GitHub
+    let shorthand = {
+        let repo = range.split('#').next().unwrap_or_default();
+        repo.matches('/').count() == 1
+            && !repo.starts_with('/')
+            && !repo.starts_with('.')
+            && !repo.contains(':')
+            && !repo.contains(' ')
+    };
+    prefixes.iter().any(|prefix| range.starts_with(prefix))
+        || suffixes.iter().any(|suffix| range.ends_with(suffix))
+        || shorthand
+}
+
+fn local_path(range: &str) -> Option<&str> {
+    ["file:", "link:", "portal:"]
+        .iter()
+        .find(|prefix| range.starts_with(*prefix))
+        .map(|prefix| &range[prefix.len()..])
+}
+
+fn escapes_workspace(path: &Path, workspace: Option<&Path>) -> bool {
+    if path.is_absolute() {
+        return workspace.map(|workspace| !path.starts_with(workspace)).unwrap_or(true);
+    }
+    let mut depth = 0;
+    for component in path.components() {
+        match component {
+            Component::ParentDir if depth == 0 => return true,
+            Component::ParentDir => depth -= 1,
+            Component::Normal(_) => depth += 1,
+            _ => (),
+        }
+    }
+    false
+}
+
+/// The key yarn sorts blocks by.
+fn sort_key(block: &Block) -> Vec<String> {
+    let mut descriptors = block.descriptors
+        .iter()
+        .map(|&(name, range)| format!("{}@{}", name, range))
+        .collect::<Vec<_>>();
+    descriptors.sort();
+    descriptors
+}
+
+/// Runs the rules enabled in `config` on the content of a `yarn.lock`, lints are sorted by position.
+pub fn lint(content: &str, config: &Config) -> Result<Vec<Lint>, Error> {
+    let blocks = read_blocks(content)?;
+    let enabled = |rule| config.rules.contains(&rule);
+    let mut lints = Vec::new();
+    let mut push = |rule, span, message: String| lints.push(Lint { rule, span, message });
+
+    for block in &blocks {
+        let headline = span_of(content, block.headline, block.line);
+        let resolved = block.resolved.and_then(|(line, resolved)| Some((line, resolved, Url::parse(resolved).ok()?)));
+        if let Some((line, resolved, url)) = resolved {
+            let span = span_of(content, resolved, line);
+            if enabled(Rule::Https) && url.scheme() == "http" {
+                push(Rule::Https, span, format!("{} is not fetched over https", url));
+            }
+            let from_registry = url.scheme() == "https" || url.scheme() == "http";
+            if enabled(Rule::AllowedHosts) && from_registry {
+                let host = url.host_str().unwrap_or_default();
+                if !config.allowed_hosts.iter().any(|allowed| allowed == host) {
+                    push(Rule::AllowedHosts, span, format!("{} is not an allowed host", host));
+                }
+            }
+        }
+
+        if enabled(Rule::Integrity) {
+            match block.integrity {
+                None => push(Rule::Integrity, headline, "no integrity".into()),
+                Some((line, integrity)) if !integrity.split_whitespace().all(|hash| hash.starts_with("sha512-")) => {
+                    push(Rule::Integrity, span_of(content, integrity, line), "integrity is not sha512".into())
+                }
+                Some(_) => (),
+            }
+        }
+
+        for &(name, range) in &block.descriptors {
+            let span = span_of(content, range, block.line);
+            if enabled(Rule::NoGitOrTarball) && local_path(range).is_none() && is_git_or_tarball(range) {
+                push(Rule::NoGitOrTarball, span, format!("{} depends on {}", name, range));
+            }
+            if enabled(Rule::FileInWorkspace) {
+                if let Some(path) = local_path(range) {
+                    if escapes_workspace(Path::new(path), config.workspace.as_deref()) {
+                        push(Rule::FileInWorkspace, span, format!("{} points outside of the workspace", path));
+                    }
+                }
+            }
+        }
+    }
+
+    if enabled(Rule::Sorted) {
+        for pair in blocks.windows(2) {
+            if sort_key(&pair[0]) > sort_key(&pair[1]) {
+                let span = span_of(content, pair[1].headline, pair[1].line);
+                push(Rule::Sorted, span, format!("should come before {}", pair[0].headline));
+            }
+        }
+    }
+
+    if enabled(Rule::MaxVersions) {
+        let mut versions: BTreeMap<&str, (Span, BTreeSet<&str>)> = BTreeMap::new();
+        for block in &blocks {
+            if let (Some(&(name, _)), Some(version)) = (block.descriptors.first(), block.version) {
+                let span = span_of(content, block.headline, block.line);
+                versions.entry(name).or_insert_with(|| (span, BTreeSet::new())).1.insert(version);
+            }
+        }
+        for (name, (span, versions)) in versions {
+            if versions.len() > config.max_versions {
+                let list = versions.into_iter().collect::<Vec<_>>().join(", ");
+                push(Rule::MaxVersions, span, format!("{} is locked in versions {}", name, list));
+            }
+        }
+    }
+
+    lints.sort_by_key(|lint| (lint.span, lint.rule));
+    Ok(lints)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCKFILE: &str = r#"# yarn lockfile v1
+
+
+left-pad@^1.0.0:
+  version "1.3.0"
+  resolved "http://registry.yarnpkg.com/left-pad/-/left-pad-1.3.0.tgz"
+  integrity sha1-W4o6d2Xf4AEmHd6RVYnngvjJTR4=
+
+chalk@^2.0.0:
+  version "2.4.2"
+  resolved "https://npm.example.com/chalk/-/chalk-2.4.2.tgz"
+
+chalk@user/chalk:
+  version "3.0.0"
+  resolved "https://codeload.github.com/user/chalk/tar.gz/abc"
+
+local@file:../../outside:
+  version "1.0.0"
+"#;
+
+    fn rules(lints: &[Lint]) -> Vec<(Rule, usize)> {
+        lints.iter().map(|lint| (lint.rule, lint.span.line)).collect()
+    }
+
+    #[test]
+    fn lints_with_spans() {
+        let lints = lint(LOCKFILE, &Config::all()).unwrap();
+        assert_eq!(
+            rules(&lints),
+            vec![
+                (Rule::Https, 6),
+                (Rule::Integrity, 7),
+                (Rule::Integrity, 9),
+                (Rule::MaxVersions, 9),
+                (Rule::Sorted, 9),
+                (Rule::AllowedHosts, 11),
+                (Rule::Integrity, 13),
+                (Rule::NoGitOrTarball, 13),
+                (Rule::AllowedHosts, 15),
+                (Rule::Integrity, 17),
+                (Rule::FileInWorkspace, 17),
+            ]
+        );
+
+        let https = &lints[0];
+        assert_eq!(https.span, Span::new(6, 13, 57));
+        let git = lints.iter().find(|lint| lint.rule == Rule::NoGitOrTarball).unwrap();
+        assert_eq!(git.span, Span::new(13, 7, 10));
+    }
+
+    #[test]
+    fn rules_can_be_turned_off() {
+        let config = Config { rules: [Rule::Https].iter().cloned().collect(), ..Config::default() };
+        assert_eq!(rules(&lint(LOCKFILE, &config).unwrap()), vec![(Rule::Https, 6)]);
+        assert_eq!("no-git-or-tarball".parse(), Ok(Rule::NoGitOrTarball));
+    }
+
+    #[test]
+    fn knows_local_paths() {
+        assert!(!escapes_workspace(Path::new("packages/a/../b"), None));
+        assert!(escapes_workspace(Path::new("packages/../../b"), None));
+        assert!(is_git_or_tarball("github:user/repo"));
+        assert!(is_git_or_tarball("user/repo#v1.0.0"));
+        assert!(!is_git_or_tarball("^1.0.0 || 2.x"));
+        assert!(!is_git_or_tarball("npm:@scope/name@^1.0.0"));
+    }
+}
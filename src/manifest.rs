@@ -0,0 +1,204 @@
+//! Minimal view on a `package.json`.
+//!
+//! Only the fields the lockfile tooling cares about are read, everything else is ignored.
+
+use serde_json::{self, Map, Value};
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use error;
+
+/// The interesting parts of a `package.json`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Manifest {
+    pub name:                  Option<String>,
+    pub version:               Option<String>,
+    pub dependencies:          BTreeMap<String, String>,
+    pub dev_dependencies:      BTreeMap<String, String>,
+    pub optional_dependencies: BTreeMap<String, String>,
+    pub peer_dependencies:     BTreeMap<String, String>,
+    /// yarn's overrides for nested packages, pattern to version
+    pub resolutions:           BTreeMap<String, String>,
+    /// globs of the workspace folders
+    pub workspaces:            Vec<String>,
+    /// globs of dependency paths that must not be hoisted, like `**/react-native`
+    pub nohoist:               Vec<String>,
+}
+
+/// A package of the project's workspaces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Workspace {
+    /// Relative to the root folder, separated by `/`
+    pub location: String,
+    pub manifest: Manifest,
+}
+
+fn string_map(object: &Map<String, Value>, key: &str) -> BTreeMap<String, String> {
+    object
+        .get(key)
+        .and_then(Value::as_object)
+        .map(|deps| {
+            deps.iter()
+                .filter_map(|(name, range)| range.as_str().map(|range| (name.clone(), range.to_string())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn strings(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(Value::as_array)
+        .map(|values| values.iter().filter_map(Value::as_str).map(ToString::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// Matches a `/` separated path against a glob, `*` stays within a segment and `**` spans any number of them.
+pub fn glob_matches(pattern: &str, path: &str) -> bool {
+    fn segment(pattern: &[u8], text: &[u8]) -> bool {
+        match pattern.split_first() {
+            None => text.is_empty(),
+            Some((b'*', rest)) => (0..=text.len()).any(|i| segment(rest, &text[i..])),
+            Some((c, rest)) => text.first() == Some(c) && segment(rest, &text[1..]),
+        }
+    }
+    fn segments(pattern: &[&str], path: &[&str]) -> bool {
+        match pattern.split_first() {
+            None => path.is_empty(),
+            Some((&"**", rest)) => (0..=path.len()).any(|i| segments(rest, &path[i..])),
+            Some((glob, rest)) => {
+                !path.is_empty() && segment(glob.as_bytes(), path[0].as_bytes()) && segments(rest, &path[1..])
+            }
+        }
+    }
+    let pattern = pattern.trim_start_matches("./").split('/').collect::<Vec<_>>();
+    segments(&pattern, &path.split('/').collect::<Vec<_>>())
+}
+
+/// Collects the folders below `dir` that contain a `package.json`, skipping `node_modules`.
+fn package_dirs(root: &Path, dir: &Path, found: &mut Vec<PathBuf>) -> Result<(), error::Error> {
+    let mut children = fs::read_dir(dir)
+        .and_then(|entries| entries.collect::<Result<Vec<_>, _>>())
+        .map_err(error::Error::io(dir))?
+        .into_iter()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect::<Vec<_>>();
+    children.sort();
+    for child in children {
+        let skipped = child.file_name().and_then(|name| name.to_str()).is_none_or(|name| {
+            name == "node_modules" || name.starts_with('.')
+        });
+        if skipped {
+            continue;
+        }
+        if child.join("package.json").is_file() {
+            found.push(child.strip_prefix(root).unwrap_or(&child).to_owned());
+        }
+        package_dirs(root, &child, found)?;
+    }
+    Ok(())
+}
+
+impl Manifest {
+    /// Parses the content of a `package.json`.
+    pub fn parse(content: &str) -> Result<Manifest, error::Error> {
+        let value: Value = serde_json::from_str(content)?;
+        let object = value.as_object().ok_or(error::Error::InvalidManifest)?;
+
+        Ok(Manifest {
+            name:                  object.get("name").and_then(Value::as_str).map(ToString::to_string),
+            version:               object.get("version").and_then(Value::as_str).map(ToString::to_string),
+            dependencies:          string_map(object, "dependencies"),
+            dev_dependencies:      string_map(object, "devDependencies"),
+            optional_dependencies: string_map(object, "optionalDependencies"),
+            peer_dependencies:     string_map(object, "peerDependencies"),
+            resolutions:           string_map(object, "resolutions"),
+            // either a list of globs or `{ "packages": [...], "nohoist": [...] }`
+            workspaces:            match object.get("workspaces") {
+                Some(Value::Object(workspaces)) => strings(workspaces.get("packages")),
+                workspaces => strings(workspaces),
+            },
+            nohoist:               strings(object.get("workspaces").and_then(|workspaces| workspaces.get("nohoist"))),
+        })
+    }
+
+    /// Opens the `package.json` of every workspace, `root` being the folder of this manifest.
+    pub fn open_workspaces<P: AsRef<Path>>(&self, root: P) -> Result<Vec<Workspace>, error::Error> {
+        if self.workspaces.is_empty() {
+            return Ok(Vec::new());
+        }
+        let root = root.as_ref();
+        let mut dirs = Vec::new();
+        package_dirs(root, root, &mut dirs)?;
+
+        let mut workspaces = Vec::new();
+        for dir in dirs {
+            let location = dir.components()
+                .map(|component| component.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+                .join("/");
+            if self.workspaces.iter().any(|pattern| glob_matches(pattern, &location)) {
+                let manifest = Manifest::open(root.join(&dir))?;
+                workspaces.push(Workspace { location, manifest });
+            }
+        }
+        Ok(workspaces)
+    }
+
+    /// Opens a given `package.json` or the one inside a folder.
+    pub fn open<P: AsRef<Path>>(given_path: P) -> Result<Manifest, error::Error> {
+        let path = given_path.as_ref();
+        let path = if path.is_dir() { path.join("package.json") } else { path.to_owned() };
+        debug!("opening {:?}", path);
+        let mut content = String::new();
+        File::open(&path)
+            .and_then(|mut file| file.read_to_string(&mut content))
+            .map_err(error::Error::io(&path))?;
+        Manifest::parse(&content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_manifest() {
+        let manifest = Manifest::parse(r#"{
+            "name": "left-pad",
+            "version": "1.3.0",
+            "dependencies": { "chalk": "^2.0.0" },
+            "devDependencies": { "tape": "*" },
+            "scripts": { "test": "tape" }
+        }"#).unwrap();
+
+        assert_eq!(manifest.name, Some("left-pad".into()));
+        assert_eq!(manifest.version, Some("1.3.0".into()));
+        assert_eq!(manifest.dependencies.get("chalk"), Some(&"^2.0.0".to_string()));
+        assert_eq!(manifest.dev_dependencies.get("tape"), Some(&"*".to_string()));
+        assert!(manifest.optional_dependencies.is_empty());
+        assert!(manifest.workspaces.is_empty());
+    }
+
+    #[test]
+    fn parses_workspaces() {
+        let listed = Manifest::parse(r#"{ "workspaces": ["packages/*"] }"#).unwrap();
+        assert_eq!(listed.workspaces, vec!["packages/*".to_string()]);
+
+        let nested = Manifest::parse(r#"{
+            "workspaces": { "packages": ["packages/*", "tools/**"], "nohoist": ["**/react-native"] }
+        }"#).unwrap();
+        assert_eq!(nested.workspaces.len(), 2);
+        assert_eq!(nested.nohoist, vec!["**/react-native".to_string()]);
+
+        assert!(glob_matches("packages/*", "packages/a"));
+        assert!(!glob_matches("packages/*", "packages/a/b"));
+        assert!(glob_matches("tools/**", "tools/a/b"));
+        assert!(glob_matches("**/react-native", "react-native"));
+        assert!(glob_matches("a/**/@babel/*", "a/b/@babel/core"));
+        assert!(!glob_matches("**/react-native", "react-native-web"));
+    }
+}
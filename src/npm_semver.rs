@@ -1,13 +1,18 @@
-use semver_parser::range::{Op, Predicate, VersionReq as VersionReqInner, WildcardVersion};
-use semver::VersionReq;
+use semver_parser::range::{self, Op, Predicate, VersionReq as VersionReqInner, WildcardVersion};
+use semver_parser::version::Identifier as ParserIdentifier;
+use semver::{Identifier, Version, VersionReq};
 use nom::IResult;
 
+use std::cmp;
 use std::str::FromStr;
 
 type Semver<'a> = (Op, u64, Option<&'a str>, Option<&'a str>);
 
+/// A version with its optional prerelease tag, like `2.0.0-0`
+type Tagged<'a> = (Semver<'a>, Option<&'a str>);
+
 /// Tuple of a lower and optinal upper version limit
-type SemverRange<'a> = (Semver<'a>, Option<Semver<'a>>);
+type SemverRange<'a> = (Tagged<'a>, Option<Tagged<'a>>);
 
 pub fn version_reqs(raw: &[u8]) -> IResult<&[u8], Vec<VersionReq>> {
     parsers::semver_range_list(raw)
@@ -18,11 +23,73 @@ pub fn version_reqs(raw: &[u8]) -> IResult<&[u8], Vec<VersionReq>> {
     )
 }
 
+/// Parses an unquoted npm range like `^1.0.0 || 2.x`, as found in a `package.json`.
+///
+/// `*`, `x` and the empty range match every version, `1.2.3 - 2.0.0` means `>=1.2.3 <=2.0.0`.
+/// Dist-tags, urls and other specifiers that are not semver ranges give `None`.
+pub fn parse_reqs(range: &str) -> Option<Vec<VersionReq>> {
+    if is_dist_tag(range) {
+        return None;
+    }
+    range.split("||").map(|alternative| parse_req(alternative.trim())).collect()
+}
+
+/// Whether `range` names a dist-tag like `latest` or `next` instead of versions.
+pub fn is_dist_tag(range: &str) -> bool {
+    range.starts_with(|c: char| c.is_ascii_alphabetic() && c != 'x' && c != 'X' && c != 'v')
+        && range.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+/// Parses one alternative of a range, without `||`.
+fn parse_req(range: &str) -> Option<VersionReq> {
+    if range.is_empty() || range == "*" || range == "x" || range == "X" {
+        return Some(VersionReq::any());
+    }
+    let quoted = match range.find(" - ") {
+        Some(at) => format!("\">={} <={}\"", range[..at].trim(), range[at + 3..].trim()),
+        None => format!("\"{}\"", range),
+    };
+    match version_reqs(quoted.as_bytes()) {
+        IResult::Done(_, ref reqs) if reqs.len() == 1 => Some(reqs[0].clone()),
+        _ => None,
+    }
+}
+
+/// Formats requirements back into npm syntax, e.g. `^1.0.0 || >=2.0.0 <3`.
+pub fn format_reqs(reqs: &[VersionReq]) -> String {
+    reqs.iter()
+        .map(|req| {
+            req.to_string()
+                .split(", ")
+                .map(|predicate| {
+                    // `semver` separates the operator by a space and spells out `=`
+                    let predicate = predicate.replacen(' ', "", 1);
+                    match predicate.strip_prefix('=') {
+                        Some(exact) => exact.to_string(),
+                        None => predicate,
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join(" || ")
+}
+
+fn parse_pre(pre: &str) -> Vec<ParserIdentifier> {
+    pre.split('.')
+        .map(|part| match part.parse() {
+            Ok(n) => ParserIdentifier::Numeric(n),
+            Err(_) => ParserIdentifier::AlphaNumeric(part.to_string()),
+        })
+        .collect()
+}
+
  fn range2req(range: SemverRange) -> VersionReqInner {
      VersionReqInner {
          predicates: Some(range.0).into_iter()
-             .chain(range.1.into_iter())
-             .map(|vers| {
+             .chain(range.1)
+             .map(|(vers, pre)| {
                  let op = match (vers.0, vers.2) {
                      (_, Some("x")) => Op::Wildcard(WildcardVersion::Minor),
                      (x, _) => x
@@ -33,13 +100,359 @@ pub fn version_reqs(raw: &[u8]) -> IResult<&[u8], Vec<VersionReq>> {
                     major: vers.1,
                     minor: vers.2.and_then(|x| FromStr::from_str(x).ok()),
                     patch: vers.3.and_then(|x| FromStr::from_str(x).ok()),
-                    pre: Vec::new(),
+                    pre: pre.map(parse_pre).unwrap_or_default(),
                 }
              })
              .collect::<Vec<Predicate>>()
       }
  }
 
+/// The versions between `lower` (inclusive) and `upper` (exclusive, unbounded if `None`).
+///
+/// Versions are ordered as in semver, so prereleases sort right before their release. Ranges that end
+/// below a release without spelling it out, like `1.2.3`, `<=1.2.3`, `~1.2.0` or `^1.0.0`, end at its
+/// first prerelease, so `^1.0.0` ends at `2.0.0-0` and leaves out `2.0.0-beta` like npm does.
+/// Otherwise the npm rule that hides prereleases from ranges that don't mention them is not modelled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interval {
+    pub lower: Version,
+    pub upper: Option<Version>,
+}
+
+impl Interval {
+    fn any() -> Interval {
+        Interval { lower: Version::new(0, 0, 0), upper: None }
+    }
+
+    fn between(lower: Version, upper: Version) -> Interval {
+        Interval { lower, upper: Some(upper) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.upper.as_ref().is_some_and(|upper| *upper <= self.lower)
+    }
+
+    pub fn contains(&self, version: &Version) -> bool {
+        *version >= self.lower && self.upper.as_ref().is_none_or(|upper| version < upper)
+    }
+
+    fn intersect(&self, other: &Interval) -> Interval {
+        let upper = match (self.upper.as_ref(), other.upper.as_ref()) {
+            (Some(a), Some(b)) => Some(cmp::min(a, b)),
+            (upper, None) | (None, upper) => upper,
+        };
+        Interval { lower: cmp::max(&self.lower, &other.lower).clone(), upper: upper.cloned() }
+    }
+
+    fn covers(&self, other: &Interval) -> bool {
+        self.lower <= other.lower && match (self.upper.as_ref(), other.upper.as_ref()) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(a), Some(b)) => b <= a,
+        }
+    }
+}
+
+fn convert_pre(pre: &[ParserIdentifier]) -> Vec<Identifier> {
+    pre.iter()
+        .map(|identifier| match *identifier {
+            ParserIdentifier::Numeric(n) => Identifier::Numeric(n),
+            ParserIdentifier::AlphaNumeric(ref s) => Identifier::AlphaNumeric(s.clone()),
+        })
+        .collect()
+}
+
+/// The lowest version a predicate can match.
+fn floor(predicate: &Predicate) -> Version {
+    let mut version = Version::new(predicate.major, predicate.minor.unwrap_or(0), predicate.patch.unwrap_or(0));
+    if predicate.patch.is_some() {
+        version.pre = convert_pre(&predicate.pre);
+    }
+    version
+}
+
+/// The first prerelease of a release, the exclusive upper bound that also leaves out its prereleases.
+fn first_prerelease(mut version: Version) -> Version {
+    if version.pre.is_empty() {
+        version.pre.push(Identifier::Numeric(0));
+    }
+    version
+}
+
+/// The next release after everything that is equal to the predicate at its precision,
+/// `1.2` is followed by `1.3.0` and `1.2.3-beta` by `1.2.3-beta.0`.
+fn next_release(predicate: &Predicate) -> Version {
+    match (predicate.minor, predicate.patch) {
+        (Some(_), Some(_)) if !predicate.pre.is_empty() => {
+            let mut version = floor(predicate);
+            version.pre.push(Identifier::Numeric(0));
+            version
+        }
+        (Some(minor), Some(patch)) => Version::new(predicate.major, minor, patch + 1),
+        (Some(minor), None) => Version::new(predicate.major, minor + 1, 0),
+        (None, _) => Version::new(predicate.major + 1, 0, 0),
+    }
+}
+
+/// The first version above everything that is equal to the predicate at its precision,
+/// `1.2.3` is followed by `1.2.4-0`, so that `1.2.4-beta` is left out.
+fn successor(predicate: &Predicate) -> Version {
+    first_prerelease(next_release(predicate))
+}
+
+fn predicate_interval(predicate: &Predicate) -> Interval {
+    let major = predicate.major;
+    let next_major = first_prerelease(Version::new(major + 1, 0, 0));
+    let next_minor = |minor: u64| first_prerelease(Version::new(major, minor + 1, 0));
+    match predicate.op {
+        Op::Ex => Interval::between(floor(predicate), successor(predicate)),
+        Op::Gt => Interval { lower: next_release(predicate), upper: None },
+        Op::GtEq => Interval { lower: floor(predicate), upper: None },
+        Op::Lt => Interval::between(Version::new(0, 0, 0), floor(predicate)),
+        Op::LtEq => Interval::between(Version::new(0, 0, 0), successor(predicate)),
+        Op::Tilde => match predicate.minor {
+            Some(minor) => Interval::between(floor(predicate), next_minor(minor)),
+            None => Interval::between(floor(predicate), next_major),
+        },
+        Op::Compatible => match (major, predicate.minor, predicate.patch) {
+            (0, Some(0), Some(_)) => Interval::between(floor(predicate), successor(predicate)),
+            (0, Some(minor), _) => Interval::between(floor(predicate), next_minor(minor)),
+            _ => Interval::between(floor(predicate), next_major),
+        },
+        Op::Wildcard(WildcardVersion::Major) => Interval::any(),
+        Op::Wildcard(WildcardVersion::Minor) => Interval::between(Version::new(major, 0, 0), next_major),
+        Op::Wildcard(WildcardVersion::Patch) => {
+            let lower = Version::new(major, predicate.minor.unwrap_or(0), 0);
+            match predicate.minor {
+                Some(minor) => Interval::between(lower, next_minor(minor)),
+                None => Interval::between(lower, next_major),
+            }
+        }
+    }
+}
+
+/// `None` if `semver_parser` can't read back what `semver` wrote.
+fn req_interval(req: &VersionReq) -> Option<Interval> {
+    // `semver` keeps the predicates private, but writes them in a syntax `semver_parser` reads
+    let predicates = range::parse(&req.to_string()).ok()?.predicates;
+    Some(predicates
+        .iter()
+        .map(predicate_interval)
+        .fold(Interval::any(), |all, interval| all.intersect(&interval)))
+}
+
+/// Whether no version lies between `upper` and `lower` but prereleases of `lower`, which the
+/// intervals leave out anyway.
+fn touches(upper: &Version, lower: &Version) -> bool {
+    lower <= upper || (lower.pre.is_empty() && first_prerelease(lower.clone()) == *upper)
+}
+
+/// Sorts intervals and merges the ones that overlap or touch, dropping empty ones.
+fn merge(mut intervals: Vec<Interval>) -> Vec<Interval> {
+    intervals.retain(|interval| !interval.is_empty());
+    intervals.sort_by(|a, b| a.lower.cmp(&b.lower));
+
+    let mut merged: Vec<Interval> = Vec::with_capacity(intervals.len());
+    for interval in intervals {
+        if let Some(last) = merged.last_mut() {
+            if last.upper.as_ref().is_none_or(|upper| touches(upper, &interval.lower)) {
+                last.upper = match (last.upper.take(), interval.upper) {
+                    (Some(a), Some(b)) => Some(cmp::max(a, b)),
+                    _ => None,
+                };
+                continue;
+            }
+        }
+        merged.push(interval);
+    }
+    merged
+}
+
+/// The sorted, disjoint intervals of the versions that satisfy any of `reqs`.
+///
+/// `None` if a requirement can't be taken apart, like every function built on it.
+pub fn intervals(reqs: &[VersionReq]) -> Option<Vec<Interval>> {
+    Some(merge(reqs.iter().map(req_interval).collect::<Option<_>>()?))
+}
+
+fn comparator(op: Op, version: &Version) -> Predicate {
+    Predicate {
+        op,
+        major: version.major,
+        minor: Some(version.minor),
+        patch: Some(version.patch),
+        pre: version.pre
+            .iter()
+            .map(|identifier| match *identifier {
+                Identifier::Numeric(n) => ParserIdentifier::Numeric(n),
+                Identifier::AlphaNumeric(ref s) => ParserIdentifier::AlphaNumeric(s.clone()),
+            })
+            .collect(),
+    }
+}
+
+/// Turns intervals back into `>=` and `<` comparator sets, one per interval.
+///
+/// Upper bounds like `2.0.0-0` are written as `<2.0.0`, which npm doesn't match `2.0.0-beta` for either.
+pub fn from_intervals(intervals: &[Interval]) -> Vec<VersionReq> {
+    intervals
+        .iter()
+        .filter(|interval| !interval.is_empty())
+        .map(|interval| {
+            let mut predicates = Vec::new();
+            if interval.lower != Version::new(0, 0, 0) {
+                predicates.push(comparator(Op::GtEq, &interval.lower));
+            }
+            if let Some(ref upper) = interval.upper {
+                let mut upper = upper.clone();
+                if upper.pre == [Identifier::Numeric(0)] {
+                    upper.pre.clear();
+                }
+                predicates.push(comparator(Op::Lt, &upper));
+            }
+            VersionReqInner { predicates }.into()
+        })
+        .collect()
+}
+
+/// The versions that satisfy both `a` and `b`.
+pub fn intersect(a: &[VersionReq], b: &[VersionReq]) -> Option<Vec<VersionReq>> {
+    let b = intervals(b)?;
+    let both = intervals(a)?
+        .iter()
+        .flat_map(|a| b.iter().map(move |b| a.intersect(b)))
+        .collect();
+    Some(from_intervals(&merge(both)))
+}
+
+/// The versions that satisfy `a` or `b`.
+pub fn union(a: &[VersionReq], b: &[VersionReq]) -> Option<Vec<VersionReq>> {
+    let mut all = intervals(a)?;
+    all.extend(intervals(b)?);
+    Some(from_intervals(&merge(all)))
+}
+
+/// Whether every version that satisfies `a` also satisfies `b`.
+pub fn is_subset(a: &[VersionReq], b: &[VersionReq]) -> Option<bool> {
+    let b = intervals(b)?;
+    Some(intervals(a)?
+        .iter()
+        .all(|a| b.iter().any(|b| b.covers(a))))
+}
+
+/// Whether no version can satisfy `reqs`, like `>2 <1` or an empty list.
+pub fn is_empty(reqs: &[VersionReq]) -> Option<bool> {
+    Some(intervals(reqs)?.is_empty())
+}
+
+/// The lowest version that satisfies `reqs`, `None` if there is none.
+pub fn min_version(reqs: &[VersionReq]) -> Option<Version> {
+    intervals(reqs)?.into_iter().next().map(|interval| interval.lower)
+}
+
+/// The intervals of `reqs` with upper bounds like `2.0.0-0` read as `2.0.0`, which is how they are spelled.
+fn canonical_intervals(reqs: &[VersionReq]) -> Option<Vec<Interval>> {
+    let intervals = intervals(reqs)?
+        .into_iter()
+        .map(|mut interval| {
+            if let Some(ref mut upper) = interval.upper {
+                if upper.pre == [Identifier::Numeric(0)] {
+                    upper.pre.clear();
+                }
+            }
+            interval
+        })
+        .collect();
+    Some(merge(intervals))
+}
+
+/// Reduces `reqs` to one `>=` and `<` comparator set per interval, so that different spellings of
+/// a range compare equal: `>= 1.0.0 < 2`, `>=1.0.0 <2.0.0-0` and `^1.0.0` all become `>=1.0.0 <2.0.0`.
+pub fn normalize(reqs: &[VersionReq]) -> Option<Vec<VersionReq>> {
+    Some(from_intervals(&canonical_intervals(reqs)?))
+}
+
+fn format_interval(interval: &Interval) -> String {
+    let lower = &interval.lower;
+    let zero = Version::new(0, 0, 0);
+    let upper = match interval.upper {
+        None if *lower == zero => return "*".to_string(),
+        None => return format!(">={}", lower),
+        Some(ref upper) if *lower == zero => return format!("<{}", upper),
+        Some(ref upper) => upper,
+    };
+
+    if lower.pre.is_empty() && upper.pre.is_empty() {
+        let (major, minor, patch) = (lower.major, lower.minor, lower.patch);
+        let caret = match (major, minor) {
+            (0, 0) => Version::new(0, 0, patch + 1),
+            (0, _) => Version::new(0, minor + 1, 0),
+            _ => Version::new(major + 1, 0, 0),
+        };
+        if *upper == Version::new(major, minor, patch + 1) {
+            return lower.to_string();
+        } else if *upper == caret {
+            return format!("^{}", lower);
+        } else if *upper == Version::new(major, minor + 1, 0) {
+            return format!("~{}", lower);
+        }
+    }
+    format!(">={} <{}", lower, upper)
+}
+
+/// Formats the normal form of `reqs` in its shortest npm spelling, e.g. `^1.0.0 || ~2.1.0`.
+pub fn canonical(reqs: &[VersionReq]) -> Option<String> {
+    let intervals = canonical_intervals(reqs)?;
+    if intervals.is_empty() {
+        return Some("<0.0.0".to_string());
+    }
+    Some(intervals.iter().map(format_interval).collect::<Vec<_>>().join(" || "))
+}
+
+/// Like node-semver's `simplifyRange`, rewrites `reqs` into a range that matches the same of the
+/// published `versions`, if that is shorter than the canonical spelling.
+///
+/// Every run of matching versions becomes `>=first <=last`, left open at the oldest and newest version.
+pub fn simplify(reqs: &[VersionReq], versions: &[Version]) -> Option<String> {
+    let mut versions = versions.to_vec();
+    versions.sort();
+    versions.dedup();
+
+    let mut runs = Vec::new();
+    let mut run: Option<(&Version, &Version)> = None;
+    for version in &versions {
+        if reqs.iter().any(|req| req.matches(version)) {
+            run = Some((run.map_or(version, |(first, _)| first), version));
+        } else if let Some(finished) = run.take() {
+            runs.push(finished);
+        }
+    }
+    runs.extend(run);
+
+    let canonical = canonical(reqs)?;
+    let (oldest, newest) = match (versions.first(), versions.last()) {
+        (Some(oldest), Some(newest)) if !runs.is_empty() => (oldest, newest),
+        _ => return Some(canonical),
+    };
+    let simplified = runs
+        .into_iter()
+        .map(|(first, last)| match (first == oldest, last == newest) {
+            _ if first == last => first.to_string(),
+            (true, true) => "*".to_string(),
+            (true, false) => format!("<={}", last),
+            (false, true) => format!(">={}", first),
+            (false, false) => format!(">={} <={}", first, last),
+        })
+        .collect::<Vec<_>>()
+        .join(" || ");
+
+    if simplified.len() < canonical.len() {
+        Some(simplified)
+    } else {
+        Some(canonical)
+    }
+}
+
 mod parsers {
     use super::*;
 
@@ -103,7 +516,7 @@ mod parsers {
         }
     }
 
-    named!{ tagged_semver(&[u8]) -> (Semver, &str),
+    named!{ tagged_semver(&[u8]) -> (Semver<'_>, &str),
 
         do_parse!(
             semver: semver >>
@@ -113,7 +526,7 @@ mod parsers {
         )
     }
 
-    named!{pub semver(&[u8]) -> Semver,
+    named!{pub semver(&[u8]) -> Semver<'_>,
 
         ws!(
         do_parse!(
@@ -127,19 +540,44 @@ mod parsers {
     }
 
 
-    named!{pub semver_range(&[u8]) -> SemverRange,
+    fn is_pre_char(c: u8) -> bool {
+        is_alphanumeric(c) || c == b'.' || c == b'-'
+    }
+
+    named!{ prerelease(&[u8]) -> &str,
+
+        complete!(
+        do_parse!(
+            tag!("-") >>
+            pre: map_res!(take_while1!(is_pre_char), from_utf8) >>
+
+            (pre)
+        )
+        )
+    }
+
+    named!{ tagged(&[u8]) -> Tagged<'_>,
+
+        do_parse!(
+            version: semver >>
+            pre: opt!(prerelease) >>
+            (version, pre)
+        )
+    }
+
+    named!{pub semver_range(&[u8]) -> SemverRange<'_>,
 
         ws!(
         do_parse!(
-            from: semver >>
+            from: tagged >>
             //tap!(opt!(alt!(tag!("<=") | tag!("<")))) >>
-            till: opt!(semver) >>
+            till: opt!(tagged) >>
             (from, till)
         )
         )
     }
 
-    named!{pub semver_range_list(&[u8]) -> Vec<SemverRange>,
+    named!{pub semver_range_list(&[u8]) -> Vec<SemverRange<'_>>,
 
         delimited!(
             char!('"'),
@@ -185,23 +623,26 @@ mod parsers {
         fn parse_semver_range() {
             assert_parser!(
                 semver_range(b">=2.3.4 < 3"), (
-                    (Op::GtEq, 2, Some("3"), Some("4")),
-               Some((Op::Lt,   3, None, None))
+                    ((Op::GtEq, 2, Some("3"), Some("4")), None),
+               Some(((Op::Lt,   3, None, None), None))
                     )
                 );
 
             let expected = (
-                    (Op::GtEq, 2, Some("3"), Some("4")),
-               Some((Op::Lt,   3, Some("2"), None))
+                    ((Op::GtEq, 2, Some("3"), Some("4")), None),
+               Some(((Op::Lt,   3, Some("2"), None), None))
                     );
 
             assert_parser!( semver_range(b">=2.3.4 < 3.2"), expected);
 
             assert_parser!( semver_range(b">=2.3.4<3.2.1"),
-                ((Op::GtEq, 2, Some("3"), Some("4")), Some((Op::Lt, 3, Some("2"), Some("1")))));
+                (((Op::GtEq, 2, Some("3"), Some("4")), None), Some(((Op::Lt, 3, Some("2"), Some("1")), None))));
 
             assert_parser!( semver_range(b">=2.3.4 < 3.2.1"),
-                ((Op::GtEq, 2, Some("3"), Some("4")), Some((Op::Lt, 3, Some("2"), Some("1")))));
+                (((Op::GtEq, 2, Some("3"), Some("4")), None), Some(((Op::Lt, 3, Some("2"), Some("1")), None))));
+
+            assert_parser!( semver_range(b">=1.0.0 <2.0.0-0"),
+                (((Op::GtEq, 1, Some("0"), Some("0")), None), Some(((Op::Lt, 2, Some("0"), Some("0")), Some("0")))));
 
 
         }
@@ -254,4 +695,123 @@ mod parsers {
     }
 }
 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reqs(range: &str) -> Vec<VersionReq> {
+        parse_reqs(range).unwrap()
+    }
+
+    fn version(version: &str) -> Version {
+        Version::parse(version).unwrap()
+    }
+
+    #[test]
+    fn parses_package_json_ranges() {
+        assert_eq!(reqs("*"), vec![VersionReq::any()]);
+        assert_eq!(reqs("x"), vec![VersionReq::any()]);
+        assert_eq!(reqs(""), vec![VersionReq::any()]);
+        assert_eq!(reqs("^1.0.0 || *"), vec![reqs("^1.0.0")[0].clone(), VersionReq::any()]);
+        assert_eq!(reqs("1.2.3 - 2.0.0"), reqs(">=1.2.3 <=2.0.0"));
+        assert!(reqs("1.2.3 - 2.3").iter().any(|req| req.matches(&version("2.3.9"))));
+
+        assert!(is_dist_tag("latest"));
+        assert!(is_dist_tag("next-11"));
+        assert!(!is_dist_tag("x"));
+        assert_eq!(parse_reqs("latest"), None);
+        assert_eq!(parse_reqs("github:user/repo"), None);
+        assert_eq!(parse_reqs("^1.0.0 || latest"), None);
+    }
+
+    #[test]
+    fn computes_intervals() {
+        assert_eq!(
+            intervals(&reqs("^1.2.0 || ~1.5.0 || 3.x")),
+            Some(vec![
+                Interval::between(version("1.2.0"), version("2.0.0-0")),
+                Interval::between(version("3.0.0"), version("4.0.0-0")),
+            ])
+        );
+        let interval = |range: &str| intervals(&reqs(range)).unwrap();
+        assert_eq!(interval("^0.2.3"), vec![Interval::between(version("0.2.3"), version("0.3.0-0"))]);
+        assert_eq!(interval("^0.0.3"), vec![Interval::between(version("0.0.3"), version("0.0.4-0"))]);
+        assert_eq!(interval(">1.2 <=2"), vec![Interval::between(version("1.3.0"), version("3.0.0-0"))]);
+        assert_eq!(interval("1 || 2"), vec![Interval::between(version("1.0.0"), version("3.0.0-0"))]);
+        assert_eq!(interval("1.2.3"), vec![Interval::between(version("1.2.3"), version("1.2.4-0"))]);
+        assert_eq!(from_intervals(&interval(">=1.0.0 <2")), reqs(">=1.0.0 <2.0.0"));
+    }
+
+    #[test]
+    fn intersects_and_unites() {
+        assert_eq!(intersect(&reqs("^1.2.0"), &reqs(">=1.5.0 <3")), Some(reqs(">=1.5.0 <2.0.0")));
+        assert_eq!(
+            intersect(&reqs("^1.0.0 || ^3.0.0"), &reqs(">=1.5.0")),
+            Some(reqs(">=1.5.0 <2.0.0 || >=3.0.0 <4.0.0"))
+        );
+        assert_eq!(intersect(&reqs("^1.0.0"), &reqs("^2.0.0")), Some(vec![]));
+        assert_eq!(union(&reqs("^1.0.0"), &reqs("^2.0.0")), Some(reqs(">=1.0.0 <3.0.0")));
+        assert_eq!(union(&reqs("~1.0.0"), &reqs("~1.2.0")), Some(reqs(">=1.0.0 <1.1.0 || >=1.2.0 <1.3.0")));
+    }
+
+    #[test]
+    fn checks_subsets() {
+        let subset = |a: &str, b: &str| is_subset(&reqs(a), &reqs(b)).unwrap();
+        assert!(subset("~1.2.3", "^1.0.0"));
+        assert!(subset("1.2.3", "^1.2.0 || ^2.0.0"));
+        assert!(subset("^1.0.0 || ^2.0.0", ">=1.0.0"));
+        assert!(!subset("^1.0.0", "~1.2.0"));
+        assert!(!subset(">=1.0.0", "^1.0.0"));
+        assert!(subset(">2 <1", "1.0.0"));
+
+        // prereleases of the next release are not in the range below it
+        assert!(!subset("1.2.4-beta", "1.2.3"));
+        assert!(!subset("1.2.4-beta", "<=1.2.3"));
+        assert!(!subset("2.0.0-beta", "^1.0.0"));
+        assert!(!subset("1.3.0-rc.1", "~1.2.0"));
+        assert!(!subset("1.0.0-beta", "^1.0.0"));
+        assert!(subset("1.2.3-beta.2", ">=1.2.3-beta <1.2.3"));
+        assert!(subset("^1.0.0", "<2.0.0"));
+    }
+
+    #[test]
+    fn finds_empty_ranges_and_minimums() {
+        assert_eq!(is_empty(&reqs(">2 <1")), Some(true));
+        assert_eq!(is_empty(&[]), Some(true));
+        assert_eq!(is_empty(&reqs("1.2.3")), Some(false));
+        assert_eq!(min_version(&reqs("^2.0.0 || >1.2")), Some(version("1.3.0")));
+        assert_eq!(min_version(&[VersionReq::any()]), Some(version("0.0.0")));
+        assert_eq!(min_version(&reqs(">2 <1")), None);
+    }
+
+    #[test]
+    fn normalizes_spellings() {
+        let canonical_form = Some(reqs(">=1.0.0 <2.0.0"));
+        assert_eq!(normalize(&reqs(">= 1.0.0 < 2")), canonical_form);
+        assert_eq!(normalize(&reqs(">=1.0.0 <2.0.0-0")), canonical_form);
+        assert_eq!(normalize(&reqs("^1.0.0")), canonical_form);
+        assert_eq!(normalize(&reqs("1.x || ^1.2.0")), canonical_form);
+
+        assert_eq!(canonical(&reqs(">= 1.0.0 < 2")).unwrap(), "^1.0.0");
+        assert_eq!(canonical(&reqs(">=0.2.3 <0.3.0-0")).unwrap(), "^0.2.3");
+        assert_eq!(canonical(&reqs(">=1.2.0 <1.3.0 || 1.2.4")).unwrap(), "~1.2.0");
+        assert_eq!(canonical(&reqs("=1.2.3 || >=2")).unwrap(), "1.2.3 || >=2.0.0");
+        assert_eq!(canonical(&reqs(">=1.0.0-beta <1.5")).unwrap(), ">=1.0.0-beta <1.5.0");
+        assert_eq!(canonical(&reqs(">2 <1")).unwrap(), "<0.0.0");
+    }
+
+    #[test]
+    fn simplifies_against_published_versions() {
+        let published = ["1.0.0", "1.1.0", "1.2.0", "2.0.0", "2.1.0", "3.0.0"]
+            .iter()
+            .map(|v| version(v))
+            .collect::<Vec<_>>();
+        assert_eq!(simplify(&reqs("1.1.0 || 1.2.0 || 2.0.0"), &published).unwrap(), ">=1.1.0 <=2.0.0");
+        assert_eq!(simplify(&reqs("^2.0.0 || ^3.0.0"), &published).unwrap(), ">=2.0.0");
+        assert_eq!(simplify(&reqs("1.0.0 || 1.1.0 || 2.1.0"), &published).unwrap(), "<=1.1.0 || 2.1.0");
+        assert_eq!(simplify(&reqs("^1.0.0"), &published).unwrap(), "^1.0.0");
+        assert_eq!(simplify(&reqs("^4.0.0"), &published).unwrap(), "^4.0.0");
+    }
+}
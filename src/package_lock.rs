@@ -0,0 +1,676 @@
+//! Conversion from and into npm's `package-lock.json`.
+
+use semver::{Version, VersionReq};
+use serde_json::{self, Map, Value};
+use url::Url;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ffi::OsStr;
+use std::path::Path;
+use std::ptr;
+
+use super::{read_file, DependencyLock};
+use entry::{Descriptor, LockEntry};
+use error;
+use graph::{lock_id, Graph};
+use hoist::{parent_location, slot, Layout};
+use manifest::Manifest;
+use npm_semver::{format_reqs, parse_reqs};
+use writer::to_yarn_lock;
+
+fn string_map<'i, I>(entries: I) -> Value
+where
+    I: IntoIterator<Item = (&'i String, String)>,
+{
+    Value::Object(entries.into_iter().map(|(k, v)| (k.clone(), Value::String(v))).collect())
+}
+
+fn root_entry(manifest: &Manifest) -> Value {
+    let mut root = Map::new();
+    if let Some(ref name) = manifest.name {
+        root.insert("name".into(), json!(name));
+    }
+    if let Some(ref version) = manifest.version {
+        root.insert("version".into(), json!(version));
+    }
+
+    let groups = [
+        ("dependencies", &manifest.dependencies),
+        ("devDependencies", &manifest.dev_dependencies),
+        ("optionalDependencies", &manifest.optional_dependencies),
+        ("peerDependencies", &manifest.peer_dependencies),
+    ];
+    for &(key, deps) in &groups {
+        if !deps.is_empty() {
+            root.insert(key.into(), string_map(deps.iter().map(|(k, v)| (k, v.clone()))));
+        }
+    }
+    Value::Object(root)
+}
+
+/// Which part of the manifest a package is installed for.
+///
+/// Like npm, a package is `optional` if every path to it passes an optional dependency and `dev` if
+/// only `devDependencies` lead to it.
+struct Flags {
+    prod:         HashSet<String>,
+    required_dev: HashSet<String>,
+    all_prod:     HashSet<String>,
+}
+
+impl Flags {
+    fn new(graph: &Graph, manifest: &Manifest) -> Flags {
+        Flags {
+            prod:         graph.required_reachable(&manifest.dependencies),
+            required_dev: graph.required_reachable(&manifest.dev_dependencies),
+            all_prod:     graph.reachable(manifest.dependencies.iter().chain(&manifest.optional_dependencies)),
+        }
+    }
+
+    fn apply(&self, lock: &DependencyLock, entry: &mut Map<String, Value>) {
+        let id = lock_id(lock);
+        if self.prod.contains(&id) {
+            return;
+        }
+        let dev = !self.all_prod.contains(&id);
+        let optional = !self.required_dev.contains(&id);
+        match (dev, optional) {
+            (true, true) => {
+                entry.insert("dev".into(), json!(true));
+                entry.insert("optional".into(), json!(true));
+            }
+            (true, false) => {
+                entry.insert("dev".into(), json!(true));
+            }
+            (false, true) => {
+                entry.insert("optional".into(), json!(true));
+            }
+            (false, false) => {
+                entry.insert("devOptional".into(), json!(true));
+            }
+        }
+    }
+}
+
+fn package_entry(lock: &DependencyLock, flags: &Flags) -> Value {
+    let mut entry = Map::new();
+    if let Some(ref version) = lock.version {
+        entry.insert("version".into(), json!(version.to_string()));
+    }
+    if let Some(ref resolved) = lock.resolved {
+        // yarn appends the sha1 as fragment, npm wants the plain tarball url
+        let mut resolved = resolved.clone();
+        resolved.set_fragment(None);
+        entry.insert("resolved".into(), json!(resolved.as_str()));
+    }
+    if let Some(ref integrity) = lock.integrity {
+        entry.insert("integrity".into(), json!(integrity));
+    }
+    flags.apply(lock, &mut entry);
+    let groups = [("dependencies", &lock.dependencies), ("optionalDependencies", &lock.optional_dependencies)];
+    for &(key, deps) in &groups {
+        if !deps.is_empty() {
+            let deps = deps.iter().map(|(name, reqs)| (name, format_reqs(reqs))).collect::<BTreeMap<_, _>>();
+            entry.insert(key.into(), string_map(deps));
+        }
+    }
+    Value::Object(entry)
+}
+
+/// Builds a `package-lock.json` with `lockfileVersion` 3 from the locks and the root `package.json`.
+///
+/// Packages are laid out the way npm would hoist them.
+pub fn to_package_lock(locks: &[DependencyLock], manifest: &Manifest) -> Value {
+    let graph = Graph::new(locks);
+    let layout = Layout::hoist(&graph, manifest);
+    let flags = Flags::new(&graph, manifest);
+
+    let mut packages = Map::new();
+    packages.insert(String::new(), root_entry(manifest));
+    for (location, lock) in &layout.packages {
+        packages.insert(location.clone(), package_entry(lock, &flags));
+    }
+
+    let mut package_lock = Map::new();
+    if let Some(ref name) = manifest.name {
+        package_lock.insert("name".into(), json!(name));
+    }
+    if let Some(ref version) = manifest.version {
+        package_lock.insert("version".into(), json!(version));
+    }
+    package_lock.insert("lockfileVersion".into(), json!(3));
+    package_lock.insert("requires".into(), json!(true));
+    package_lock.insert("packages".into(), Value::Object(packages));
+    Value::Object(package_lock)
+}
+
+/// One installed package of a `package-lock.json`, independent of the `lockfileVersion`.
+#[derive(Debug, Default)]
+struct NpmPackage {
+    name:         String,
+    version:      Option<String>,
+    resolved:     Option<String>,
+    integrity:    Option<String>,
+    dependencies: BTreeMap<String, String>,
+    optional_dependencies: BTreeMap<String, String>,
+}
+
+impl NpmPackage {
+    /// Required and optional dependencies alike.
+    fn requires(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.dependencies.iter().chain(&self.optional_dependencies)
+    }
+}
+
+fn str_field(entry: &Value, key: &str) -> Option<String> {
+    entry.get(key).and_then(Value::as_str).map(ToString::to_string)
+}
+
+fn str_map(entry: &Value, key: &str) -> BTreeMap<String, String> {
+    entry
+        .get(key)
+        .and_then(Value::as_object)
+        .map(|map| {
+            map.iter()
+                .filter_map(|(name, range)| range.as_str().map(|range| (name.clone(), range.to_string())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// `node_modules/a/node_modules/@s/b` is `@s/b`.
+fn name_from_location(location: &str) -> Option<&str> {
+    location.rfind("node_modules/").map(|i| &location[i + "node_modules/".len()..])
+}
+
+/// Reads the flat `packages` map of `lockfileVersion` 2 and 3.
+fn read_packages(packages: &Map<String, Value>) -> BTreeMap<String, NpmPackage> {
+    packages
+        .iter()
+        .filter(|&(_, entry)| !entry.get("link").and_then(Value::as_bool).unwrap_or(false))
+        // bundled packages come inside the tarball of their parent
+        .filter(|&(_, entry)| !entry.get("inBundle").and_then(Value::as_bool).unwrap_or(false))
+        .filter_map(|(location, entry)| {
+            let name = str_field(entry, "name")
+                .or_else(|| name_from_location(location).map(ToString::to_string))
+                .or_else(|| if location.is_empty() { Some(String::new()) } else { None });
+            let mut dependencies = str_map(entry, "dependencies");
+            if location.is_empty() {
+                dependencies.extend(str_map(entry, "devDependencies"));
+            }
+
+            name.map(|name| {
+                let package = NpmPackage {
+                    name,
+                    version: str_field(entry, "version"),
+                    resolved: str_field(entry, "resolved"),
+                    integrity: str_field(entry, "integrity"),
+                    dependencies,
+                    optional_dependencies: str_map(entry, "optionalDependencies"),
+                };
+                (location.clone(), package)
+            })
+        })
+        .collect()
+}
+
+/// Flattens the nested `dependencies` tree of `lockfileVersion` 1 into locations.
+fn read_dependencies_tree(location: &str, dependencies: &Map<String, Value>, packages: &mut BTreeMap<String, NpmPackage>) {
+    for (name, entry) in dependencies {
+        let package_location = slot(location, name);
+        packages.insert(
+            package_location.clone(),
+            NpmPackage {
+                name:         name.clone(),
+                version:      str_field(entry, "version"),
+                resolved:     str_field(entry, "resolved"),
+                integrity:    str_field(entry, "integrity"),
+                dependencies: str_map(entry, "requires"),
+                optional_dependencies: BTreeMap::new(),
+            },
+        );
+        if let Some(nested) = entry.get("dependencies").and_then(Value::as_object) {
+            read_dependencies_tree(&package_location, nested, packages);
+        }
+    }
+}
+
+/// Finds the location node would load `name` from when required at `location`.
+fn lookup<'p>(packages: &'p BTreeMap<String, NpmPackage>, location: &str, name: &str) -> Option<&'p str> {
+    let mut current = Some(location);
+    while let Some(location) = current {
+        if let Some((found, _)) = packages.get_key_value(&slot(location, name)) {
+            return Some(found);
+        }
+        current = parent_location(location);
+    }
+    None
+}
+
+/// Every range that resolves to a location, these become the headlines in a `yarn.lock`.
+fn collect_ranges(packages: &BTreeMap<String, NpmPackage>) -> BTreeMap<&str, BTreeSet<&str>> {
+    let mut ranges: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for (location, package) in packages {
+        for (name, range) in package.requires() {
+            match lookup(packages, location, name) {
+                Some(target) => {
+                    ranges.entry(target).or_default().insert(range);
+                }
+                None => warn!("{:?} requires {}, which is not installed", location, name),
+            }
+        }
+    }
+    ranges
+}
+
+/// The ranges of all installations of each `name@version`.
+fn installed_ranges<'p>(
+    packages: &'p BTreeMap<String, NpmPackage>,
+    ranges: &BTreeMap<&'p str, BTreeSet<&'p str>>,
+) -> HashMap<(&'p str, Option<&'p str>), BTreeSet<&'p str>> {
+    let mut installed: HashMap<_, BTreeSet<&str>> = HashMap::new();
+    for (location, package) in packages {
+        let key = (package.name.as_str(), package.version.as_deref());
+        let ranges = ranges.get(location.as_str()).into_iter().flatten().cloned();
+        installed.entry(key).or_default().extend(ranges);
+    }
+    installed
+}
+
+/// Looks up the ranges of `package` collected by `installed_ranges`.
+fn ranges_of<'p>(
+    installed: &HashMap<(&'p str, Option<&'p str>), BTreeSet<&'p str>>,
+    package: &NpmPackage,
+) -> BTreeSet<&'p str> {
+    let key = (package.name.as_str(), package.version.as_deref());
+    installed.get(&key).cloned().unwrap_or_default()
+}
+
+/// The installed packages without the root, each `name@version` only once.
+fn unique_packages(packages: &BTreeMap<String, NpmPackage>) -> Vec<&NpmPackage> {
+    let mut seen = HashSet::new();
+    packages
+        .iter()
+        .filter(|&(location, _)| !location.is_empty())
+        .map(|(_, package)| package)
+        .filter(|package| seen.insert((&package.name, &package.version)))
+        .collect()
+}
+
+fn parse_ranges(ranges: &BTreeMap<String, String>) -> HashMap<String, Vec<VersionReq>> {
+    ranges
+        .iter()
+        .filter_map(|(name, range)| match parse_reqs(range) {
+            Some(reqs) => Some((name.clone(), reqs)),
+            None => {
+                warn!("INVALID Dependency {} {:?}", name, range);
+                None
+            }
+        })
+        .collect()
+}
+
+fn to_locks(packages: &BTreeMap<String, NpmPackage>) -> Vec<DependencyLock> {
+    let ranges = collect_ranges(packages);
+    let installed = installed_ranges(packages, &ranges);
+
+    let mut locks = Vec::new();
+    for package in unique_packages(packages) {
+        let lock = DependencyLock {
+            name: package.name.clone(),
+            last_seen: None,
+            version: package.version.as_ref().and_then(|v| Version::parse(v).ok()),
+            resolved: package.resolved.as_ref().and_then(|r| Url::parse(r).ok()),
+            integrity: package.integrity.clone(),
+            dependencies: parse_ranges(&package.dependencies),
+            optional_dependencies: parse_ranges(&package.optional_dependencies),
+        };
+
+        let mut last_seen = ranges_of(&installed, package)
+            .into_iter()
+            .map(|range| VersionReq::parse(range).ok())
+            .collect::<Vec<_>>();
+        if last_seen.is_empty() {
+            debug!("nothing requires {}", package.name);
+            last_seen.push(None);
+        }
+
+        locks.extend(last_seen.into_iter().map(|last_seen| DependencyLock {
+            last_seen,
+            name: lock.name.clone(),
+            version: lock.version.clone(),
+            resolved: lock.resolved.clone(),
+            integrity: lock.integrity.clone(),
+            dependencies: lock.dependencies.clone(),
+            optional_dependencies: lock.optional_dependencies.clone(),
+        }));
+    }
+    locks
+}
+
+fn read_package_lock(content: &str) -> Result<BTreeMap<String, NpmPackage>, error::Error> {
+    let value: Value = serde_json::from_str(content)?;
+
+    if let Some(packages) = value.get("packages").and_then(Value::as_object) {
+        Ok(read_packages(packages))
+    } else if let Some(dependencies) = value.get("dependencies").and_then(Value::as_object) {
+        let mut packages = BTreeMap::new();
+        read_dependencies_tree("", dependencies, &mut packages);
+        Ok(packages)
+    } else if value.get("lockfileVersion").and_then(Value::as_u64) == Some(1) {
+        // npm leaves out `dependencies` when nothing is installed
+        Ok(BTreeMap::new())
+    } else {
+        let version = value.get("lockfileVersion").map(ToString::to_string).unwrap_or_default();
+        Err(error::Error::UnsupportedFormat(format!("package-lock.json version {:?}", version)))
+    }
+}
+
+/// Parses the content of a `package-lock.json` (`lockfileVersion` 1, 2 or 3) into `DependencyLock`s.
+///
+/// Version 2 and 3 are read from `packages`, version 1 from the nested `dependencies`.
+pub fn parse_package_lock(content: &str) -> Result<Vec<DependencyLock>, error::Error> {
+    read_package_lock(content).map(|packages| to_locks(&packages))
+}
+
+/// Turns the content of a `package-lock.json` into a `yarn.lock`, without resolving anything again.
+///
+/// Each installed version becomes one block whose headline lists every range that resolves to it.
+/// A range that resolves to several versions is only kept for the highest one.
+/// `lockfileVersion` 1 does not record the root requirements, pass the `package.json` to get those.
+pub fn package_lock_to_yarn_lock(content: &str, manifest: Option<&Manifest>) -> Result<String, error::Error> {
+    let mut packages = read_package_lock(content)?;
+    if let Some(manifest) = manifest {
+        let root = packages.entry(String::new()).or_insert_with(NpmPackage::default);
+        for (name, range) in manifest.dependencies.iter().chain(&manifest.dev_dependencies) {
+            root.dependencies.entry(name.clone()).or_insert_with(|| range.clone());
+        }
+        for (name, range) in &manifest.optional_dependencies {
+            root.optional_dependencies.entry(name.clone()).or_insert_with(|| range.clone());
+        }
+    }
+    let ranges = collect_ranges(&packages);
+    let installed = installed_ranges(&packages, &ranges);
+    let unique = unique_packages(&packages);
+
+    // a tree that is not deduped can install several versions for one range, but a `yarn.lock` has one
+    // block per descriptor, so the highest version gets it like in yarn
+    let version_of = |package: &NpmPackage| package.version.as_ref().and_then(|v| Version::parse(v).ok());
+    let mut owners: HashMap<(&str, &str), &NpmPackage> = HashMap::new();
+    for &package in &unique {
+        for range in ranges_of(&installed, package) {
+            let owner = owners.entry((package.name.as_str(), range)).or_insert(package);
+            if !ptr::eq(*owner, package) {
+                let (kept, dropped) =
+                    if version_of(package) > version_of(owner) { (package, *owner) } else { (*owner, package) };
+                warn!(
+                    "{}@{} resolves to {:?} and {:?}, keeping {:?}",
+                    package.name, range, kept.version, dropped.version, kept.version
+                );
+                *owner = kept;
+            }
+        }
+    }
+
+    let entries = unique
+        .into_iter()
+        .filter_map(|package| {
+            let ranges = ranges_of(&installed, package);
+            let mut descriptors = ranges
+                .iter()
+                .filter(|&&range| ptr::eq(owners[&(package.name.as_str(), range)], package))
+                .map(|&range| Descriptor::new(package.name.clone(), range))
+                .collect::<Vec<_>>();
+            if descriptors.is_empty() {
+                if !ranges.is_empty() {
+                    // every range went to a higher version
+                    return None;
+                }
+                if let Some(ref version) = package.version {
+                    descriptors.push(Descriptor::new(package.name.clone(), version.clone()));
+                }
+            }
+
+            Some(LockEntry {
+                descriptors,
+                version: package.version.clone(),
+                resolved: package.resolved.clone(),
+                integrity: package.integrity.clone(),
+                dependencies: package.dependencies.clone(),
+                optional_dependencies: package.optional_dependencies.clone(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(to_yarn_lock(&entries))
+}
+
+/// Opens a given `package-lock.json` or the one inside a folder.
+pub fn open_package_lock<P: AsRef<OsStr> + Sized>(given_path: P) -> Result<Vec<DependencyLock>, error::Error> {
+    let path = Path::new(&given_path);
+    read_file(path, "package-lock.json").and_then(|s| parse_package_lock(&s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn lock(name: &str, version: &str, dependencies: &[(&str, &str)]) -> DependencyLock {
+        DependencyLock {
+            name:         name.into(),
+            last_seen:    None,
+            version:      Version::parse(version).ok(),
+            resolved:     format!("https://registry.yarnpkg.com/{0}/-/{0}-{1}.tgz#abc", name, version).parse().ok(),
+            integrity:    Some("sha512-xyz".into()),
+            dependencies: dependencies
+                .iter()
+                .map(|&(n, r)| (n.to_string(), parse_reqs(r).unwrap()))
+                .collect::<HashMap<_, _>>(),
+            optional_dependencies: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn hoists_and_flags_packages() {
+        let locks = vec![
+            lock("a", "1.0.0", &[("c", "^1.0.0")]),
+            lock("b", "1.0.0", &[("c", "^2.0.0")]),
+            lock("c", "1.1.0", &[]),
+            lock("c", "2.0.0", &[]),
+        ];
+        let manifest = Manifest::parse(r#"{
+            "name": "root",
+            "dependencies": { "a": "^1.0.0" },
+            "devDependencies": { "b": "^1.0.0" }
+        }"#).unwrap();
+
+        let package_lock = to_package_lock(&locks, &manifest);
+        let packages = &package_lock["packages"];
+
+        assert_eq!(package_lock["lockfileVersion"], json!(3));
+        assert_eq!(packages["node_modules/c"]["version"], json!("1.1.0"));
+        assert_eq!(packages["node_modules/b/node_modules/c"]["version"], json!("2.0.0"));
+        assert_eq!(packages["node_modules/b/node_modules/c"]["dev"], json!(true));
+        assert_eq!(packages["node_modules/a"].get("dev"), None);
+        assert_eq!(packages["node_modules/a"]["dependencies"]["c"], json!("^1.0.0"));
+        assert_eq!(packages["node_modules/a"]["resolved"], json!("https://registry.yarnpkg.com/a/-/a-1.0.0.tgz"));
+    }
+
+    #[test]
+    fn flags_optional_packages() {
+        let mut a = lock("a", "1.0.0", &[]);
+        a.optional_dependencies.insert("fsevents".into(), parse_reqs("^1.2.0").unwrap());
+        let locks = vec![
+            a,
+            lock("fsevents", "1.2.7", &[("nan", "^2.0.0")]),
+            lock("nan", "2.14.0", &[]),
+            lock("b", "1.0.0", &[("nan", "^2.0.0")]),
+        ];
+        let manifest = Manifest::parse(r#"{
+            "dependencies": { "a": "^1.0.0" },
+            "devDependencies": { "b": "^1.0.0" }
+        }"#).unwrap();
+
+        let package_lock = to_package_lock(&locks, &manifest);
+        let packages = &package_lock["packages"];
+
+        assert_eq!(packages["node_modules/a"]["optionalDependencies"]["fsevents"], json!("^1.2.0"));
+        assert_eq!(packages["node_modules/a"].get("optional"), None);
+        assert_eq!(packages["node_modules/fsevents"]["optional"], json!(true));
+        assert_eq!(packages["node_modules/nan"]["devOptional"], json!(true));
+        assert_eq!(packages["node_modules/b"]["dev"], json!(true));
+    }
+
+    #[test]
+    fn reads_package_lock_v3() {
+        let locks = parse_package_lock(r#"{
+            "lockfileVersion": 3,
+            "packages": {
+                "": { "name": "root", "dependencies": { "a": "^1.0.0", "c": "^1.0.0" } },
+                "node_modules/a": {
+                    "version": "1.0.0",
+                    "resolved": "https://registry.npmjs.org/a/-/a-1.0.0.tgz",
+                    "integrity": "sha512-aaa",
+                    "dependencies": { "c": "^2.0.0" }
+                },
+                "node_modules/a/node_modules/c": { "version": "2.0.0" },
+                "node_modules/c": { "version": "1.1.0" }
+            }
+        }"#).unwrap();
+
+        let summary = locks
+            .iter()
+            .map(|lock| (lock.name.as_str(), lock.version.as_ref().map(ToString::to_string), lock.last_seen.is_some()))
+            .collect::<Vec<_>>();
+        assert_eq!(summary, vec![
+            ("a", Some("1.0.0".into()), true),
+            ("c", Some("2.0.0".into()), true),
+            ("c", Some("1.1.0".into()), true),
+        ]);
+        assert_eq!(locks[0].integrity, Some("sha512-aaa".into()));
+        assert_eq!(locks[0].dependencies["c"], parse_reqs("^2.0.0").unwrap());
+    }
+
+    #[test]
+    fn reads_package_lock_v1() {
+        let locks = parse_package_lock(r#"{
+            "lockfileVersion": 1,
+            "dependencies": {
+                "a": {
+                    "version": "1.0.0",
+                    "requires": { "c": "^2.0.0" },
+                    "dependencies": { "c": { "version": "2.0.0" } }
+                },
+                "c": { "version": "1.1.0" }
+            }
+        }"#).unwrap();
+
+        assert_eq!(locks.len(), 3);
+        assert_eq!(locks[1].name, "c");
+        assert_eq!(locks[1].version, Version::parse("2.0.0").ok());
+        assert_eq!(locks[2].last_seen, None);
+
+        assert!(parse_package_lock(r#"{ "lockfileVersion": 1 }"#).unwrap().is_empty());
+    }
+
+    #[test]
+    fn keeps_optional_dependencies_apart() {
+        let locks = parse_package_lock(r#"{
+            "lockfileVersion": 3,
+            "packages": {
+                "": { "dependencies": { "a": "^1.0.0" } },
+                "node_modules/a": {
+                    "version": "1.0.0",
+                    "dependencies": { "c": "^1.0.0" },
+                    "optionalDependencies": { "fsevents": "^1.2.0" }
+                },
+                "node_modules/c": { "version": "1.1.0" },
+                "node_modules/fsevents": { "version": "1.2.7", "optional": true }
+            }
+        }"#).unwrap();
+
+        assert_eq!(locks[0].dependencies.keys().collect::<Vec<_>>(), vec!["c"]);
+        assert_eq!(locks[0].optional_dependencies["fsevents"], parse_reqs("^1.2.0").unwrap());
+    }
+
+    #[test]
+    fn converts_package_lock_to_yarn_lock() {
+        let yarn_lock = package_lock_to_yarn_lock(r#"{
+            "lockfileVersion": 2,
+            "packages": {
+                "": { "dependencies": { "a": "^1.0.0", "c": "^2.0.0" } },
+                "node_modules/a": {
+                    "version": "1.0.0",
+                    "resolved": "https://registry.npmjs.org/a/-/a-1.0.0.tgz",
+                    "integrity": "sha512-aaa",
+                    "dependencies": { "c": "^2.1.0" }
+                },
+                "node_modules/c": { "version": "2.1.0" }
+            }
+        }"#, None).unwrap();
+
+        assert_eq!(yarn_lock, r#"# THIS IS AN AUTOGENERATED FILE. DO NOT EDIT THIS FILE DIRECTLY.
+# yarn lockfile v1
+
+
+a@^1.0.0:
+  version "1.0.0"
+  resolved "https://registry.npmjs.org/a/-/a-1.0.0.tgz"
+  integrity sha512-aaa
+  dependencies:
+    c "^2.1.0"
+
+c@^2.0.0, c@^2.1.0:
+  version "2.1.0"
+"#);
+    }
+
+    #[test]
+    fn skips_bundled_packages() {
+        let yarn_lock = package_lock_to_yarn_lock(r#"{
+            "lockfileVersion": 3,
+            "packages": {
+                "": { "dependencies": { "a": "^1.0.0" } },
+                "node_modules/a": { "version": "1.0.0", "bundleDependencies": ["b"], "dependencies": { "b": "^1.0.0" } },
+                "node_modules/a/node_modules/b": { "version": "1.0.0", "inBundle": true }
+            }
+        }"#, None).unwrap();
+
+        assert_eq!(yarn_lock, r#"# THIS IS AN AUTOGENERATED FILE. DO NOT EDIT THIS FILE DIRECTLY.
+# yarn lockfile v1
+
+
+a@^1.0.0:
+  version "1.0.0"
+  dependencies:
+    b "^1.0.0"
+"#);
+    }
+
+    #[test]
+    fn keeps_one_version_per_descriptor() {
+        let yarn_lock = package_lock_to_yarn_lock(r#"{
+            "lockfileVersion": 3,
+            "packages": {
+                "": { "dependencies": { "a": "^1.0.0", "c": "^1.0.0" } },
+                "node_modules/a": { "version": "1.0.0", "dependencies": { "c": "^1.0.0" } },
+                "node_modules/a/node_modules/c": { "version": "1.0.0" },
+                "node_modules/c": { "version": "1.1.0" }
+            }
+        }"#, None).unwrap();
+
+        assert_eq!(yarn_lock, r#"# THIS IS AN AUTOGENERATED FILE. DO NOT EDIT THIS FILE DIRECTLY.
+# yarn lockfile v1
+
+
+a@^1.0.0:
+  version "1.0.0"
+  dependencies:
+    c "^1.0.0"
+
+c@^1.0.0:
+  version "1.1.0"
+"#);
+    }
+}
@@ -1,325 +1,262 @@
-#![allow(unused_parens)]
+//! Parses `yarn.lock` files, see `lexer` for the syntax.
 
-use indent_tokenizer::{tokenize, Token};
-use nom::{line_ending, IResult};
-use semver::{Version, VersionReq};
-use url::Url;
 use multimap::MultiMap;
-
-use std::collections::HashMap;
-use std::str::from_utf8;
-use std::ops::Deref;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 
 use super::DependencyLock;
+use borrowed::{Entry, Lockfile};
+use entry::LockEntry;
 use error;
-use npm_semver::version_reqs;
-
-fn read_versionreq(tokens: &[Token]) -> Option<VersionReq> {
-    tokens
-        .iter()
-        .flat_map(|t| &t.lines)
-        .filter_map(|line| {
-            let tup_line = versionreq_line(line);
-            if let IResult::Done(_left_overs, tup_line) = tup_line {
-                Some(tup_line)
-            } else {
-                error!("INVALID VersionRequirement {:?}", line);
-                None
-            }
-        })
-        .nth(0)
-}
-
-fn read_version_resolved(tokens: &[Token]) -> (Option<Version>, Option<Url>) {
-    let mut version = None;
-    let mut resolved = None;
-    for line in tokens.iter().flat_map(|t| &t.lines) {
-        let tup_line = version_line(line);
-        if let IResult::Done(_left_overs, ver) = tup_line {
-            version = Some(ver);
-        }
-        let tup_line = resolved_line(line);
-        if let IResult::Done(_left_overs, res) = tup_line {
-            resolved = Some(res);
-        }
-    }
-    (version, resolved)
-}
-
-fn read_dependencies(tokens: &[Token]) -> HashMap<String, Vec<VersionReq>> {
-    tokens
-        .iter()
-        .filter(|token| {
-            token
-                .lines
-                .last()
-                .map(|s| s.starts_with("dependencies"))
-                .unwrap_or(false)
-        })
-        .flat_map(|t| &t.tokens)
-        .nth(0)
-        .iter()
-        .flat_map(|t| &t.lines)
-        .filter_map(|line| {
-            let tup_line = dependency_line(line);
-            if let IResult::Done(_left_overs, tup_line) = tup_line {
-                let (key, val) = tup_line;
-                Some((key.to_string(), val))
-            } else {
-                error!("INVALID Depedency {}", line.deref());
-                None
-            }
-        })
-        .collect()
-}
-
-fn read_block(block: &Token) -> Vec<DependencyLock> {
-    let dependencies = read_dependencies(&block.tokens);
-    let (version, resolved) = read_version_resolved(&block.tokens);
-
-    block
-        .lines
-        .iter()
-        .filter(|l| !l.starts_with('#'))
-        .flat_map(|heading| {
-            let (_, head_lines) = headline_parts(&heading).unwrap();
-            head_lines
-                .into_iter()
-                .map(|(last_seen, name)| DependencyLock {
-                    name: name.unwrap().to_string(),
-                    last_seen: last_seen.and_then(|s| VersionReq::parse(s).ok()),
-                    version: version.clone(),
-                    resolved: resolved.clone(),
-                    dependencies: dependencies.clone(),
-                })
-        })
-        .collect()
-}
 
-fn split_at_last_at(s: &str) -> (Option<&str>, Option<&str>) {
-    let mut it = s.rsplitn(2, '@');
-    (it.nth(0), it.nth(0))
+/// Parses content of a `yarn.lock` into its entries, keeping every value as written.
+///
+/// Unlike `parse` this keeps merged headlines together, so the result can be written back with `writer`.
+pub fn parse_entries(content: &str) -> Result<Vec<LockEntry>, error::Error> {
+    Ok(Lockfile::parse(content)?.entries.iter().map(Entry::to_entry).collect())
 }
 
-/// Parses content of a `yarn.lock` into a `Vec<DepdencencyLock>`.
+/// Parses content of a `yarn.lock` into a `Vec<DepdencencyLock>`, one per descriptor.
 pub fn parse(content: &str) -> Result<Vec<DependencyLock>, error::Error> {
-    Ok(tokenize(content)
-        .map_err(error::IndentationFail)?
-        .iter()
-        .flat_map(read_block)
-        .collect())
+    Ok(Lockfile::parse(content)?.to_locks())
 }
 
 /// Parses content of a `yarn.lock` and maps the linto a `MultiMap<Strign, DepdencencyLock>`.
 pub fn parse_by_name(content: &str) -> Result<MultiMap<String, DependencyLock>, error::Error> {
-    Ok(tokenize(content)
-        .map_err(error::IndentationFail)?
-        .iter()
-        .flat_map(read_block)
-        .map(|lock| (lock.name.clone(), lock))
-        .collect())
-}
-
-fn headline_parts(content: &str) -> IResult<&[u8], Vec<(Option<&str>, Option<&str>)>> {
-    at_tuple_list(content.as_bytes())
-}
-
-named!{
-headline(&[u8]) -> &str,
-    do_parse!(
-        content: map_res!(take_till_s!(|c| c == ':' as u8), from_utf8) >>
-        opt!(line_ending) >>
-        (content)
-    )
+    Ok(parse(content)?.into_iter().map(|lock| (lock.name.clone(), lock)).collect())
 }
 
-named!{
-at_tuple(&[u8]) -> (Option<&str>, Option<&str>),
-    map!(
-        map_res!(
-            alt!( is_not!(",\"")
-                | delimited!(char!('"'), is_not!(":\""), char!('"'))
-                )
-        , from_utf8)
-    , split_at_last_at)
-}
-
-named!{
-at_tuple_list(&[u8]) -> Vec<(Option<&str>, Option<&str>)>,
-    separated_list_complete!(ws!(tag!(",")), at_tuple)
-}
-
-// named!{
-// headline_parts2_int(&[u8]) -> (Option<&str>, Option<VersionReq>),
-//          alt!(
-//              map!( map_res!(is_not!(":,\""), from_utf8), split_at_last_at)
-//              |
-//              map!( map_res!(delimited!(char!('"'), is_not!(":,\""), char!('"')), from_utf8), split_at_last_at)
-//              )
-// }
-
-fn dependency_line(content: &str) -> IResult<&[u8], (&str, Vec<VersionReq>)> {
-    dependency_line_int(content.as_bytes())
-}
-
-named!{ quoted_string(&[u8]) -> (&str),
-    alt!( map_res!(delimited!(char!('"'), is_not!(",\""), char!('"')), from_utf8)
-        | map_res!(is_not!(" "), from_utf8)
-    )
-}
-
-named!{ dependency_line_int(&[u8]) -> (&str, Vec<VersionReq>),
-
-    ws!(
-    tuple!(quoted_string, version_reqs)
-    )
-
-}
-
-fn version_line(content: &str) -> IResult<&[u8], Version> {
-    version_line_int(content.as_bytes())
-}
-
-named!{
-version_line_int(&[u8]) -> Version,
-    map_res!(
-    ws!(do_parse!(
-        tag!("version") >>
-        version: quoted_string
-        >> (version)
-        )
-    ), Version::parse)
-}
-
-fn versionreq_line(content: &str) -> IResult<&[u8], VersionReq> {
-    versionreq_line_int(content.as_bytes())
+/// Splits `content` into about `parts` chunks of whole top-level blocks.
+///
+/// Chunks only start at an unindented line right after a blank line, which is where yarn separates blocks.
+fn split_blocks(content: &str, parts: usize) -> Vec<&str> {
+    let target = content.len() / parts.max(1);
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    let mut after_blank = false;
+
+    for line in content.split_inclusive('\n') {
+        let blank = line.trim().is_empty();
+        if after_blank && !blank && !line.starts_with(char::is_whitespace) && offset - start > target {
+            chunks.push(&content[start..offset]);
+            start = offset;
+        }
+        after_blank = blank;
+        offset += line.len();
+    }
+    chunks.push(&content[start..]);
+    chunks
 }
 
-named!{
-versionreq_line_int(&[u8]) -> VersionReq,
-    map_res!(
-    ws!(do_parse!(
-        tag!("version")
-        >> versionreq: quoted_string
-        >> (versionreq)
-        )
-    ), VersionReq::parse)
+/// Smaller chunks are not worth sending to another thread.
+#[cfg(feature = "rayon")]
+const MIN_CHUNK_LEN: usize = 16 * 1024;
+
+/// Like `parse_entries`, but parses chunks of blocks on the `rayon` thread pool.
+///
+/// Entries come out in the same order and errors are the same as those of `parse_entries`.
+#[cfg(feature = "rayon")]
+pub fn parse_entries_parallel(content: &str) -> Result<Vec<LockEntry>, error::Error> {
+    let parts = (rayon::current_num_threads() * 4).min(content.len() / MIN_CHUNK_LEN);
+    parse_chunks(&split_blocks(content, parts))
 }
 
-fn resolved_line(content: &str) -> IResult<&[u8], Url> {
-    resolved_line_int(content.as_bytes())
+/// Parses the chunks of `split_blocks` in parallel and joins their entries.
+///
+/// Fails with the error of the first chunk that fails, counting lines from the start of the content.
+#[cfg(feature = "rayon")]
+fn parse_chunks(chunks: &[&str]) -> Result<Vec<LockEntry>, error::Error> {
+    let parsed = chunks.par_iter().map(|chunk| parse_entries(chunk)).collect::<Vec<_>>();
+    let mut entries = Vec::new();
+    let mut lines = 0;
+    for (chunk, result) in chunks.iter().zip(parsed) {
+        entries.extend(result.map_err(|error| error.offset_lines(lines))?);
+        lines += chunk.matches('\n').count();
+    }
+    Ok(entries)
 }
 
-named!{
-resolved_line_int(&[u8]) -> Url,
-        map_res!(
-        ws!(do_parse!(
-            tag!("resolved") >>
-            resolved: alt!( map_res!(delimited!(char!('"'), is_not!(",\""), char!('"')), from_utf8)
-                          | map_res!(is_not!(" "), from_utf8)
-                          )
-            >> (resolved)
-            )
-        ), Url::parse)
+/// Like `parse`, but in parallel, see `parse_entries_parallel`.
+#[cfg(feature = "rayon")]
+pub fn parse_parallel(content: &str) -> Result<Vec<DependencyLock>, error::Error> {
+    Ok(parse_entries_parallel(content)?
+        .par_iter()
+        .flat_map_iter(LockEntry::to_locks)
+        .collect())
 }
 
 #[cfg(test)]
 mod tests {
-    #![allow(unused_macros)]
-
     use super::*;
+    use entry::Descriptor;
+    use lexer::split_key_value;
+    use npm_semver::parse_reqs;
+    use semver::{Version, VersionReq};
+    use url::Url;
+
+    use std::collections::HashMap;
 
     fn test_file() -> &'static str {
         include_str!("../yarn.lock.big")
     }
 
-    macro_rules! assert_parser {
-        ($parser: expr, $expected: expr) => {
-            assert_eq!($parser, IResult::Done(&[][..], $expected));
-        };
+    /// Blocks as yarn writes them, with the quoting and ranges that used to trip up the line parsers.
+    const FIXTURE: &str = r#"# THIS IS AN AUTOGENERATED FILE. DO NOT EDIT THIS FILE DIRECTLY.
+# yarn lockfile v1
+
+
+"@babel/code-frame@^7.0.0", "@babel/code-frame@^7.8.3":
+  version "7.8.3"
+  resolved "https://registry.yarnpkg.com/@babel/code-frame/-/code-frame-7.8.3.tgz#33e25903d7481181534e12ec0a25f16b6fcf419e"
+  integrity sha512-a9gxpmdXtZEInkCSHUJDLHZVBgb1QS0jhss4cPP93EW7s+uC5bikET2twEF3KV+7rDblJcmNvTR7VJejqd2C2g==
+  dependencies:
+    "@babel/highlight" "^7.8.3"
+
+chokidar@^2.1.8:
+  version "2.1.8"
+  resolved "https://registry.yarnpkg.com/chokidar/-/chokidar-2.1.8.tgz#804b3a7b6a99358c3c5c61e71d8728f041cff917"
+  dependencies:
+    anymatch "^2.0.0"
+    "glob-parent" "^3.1.0"
+    normalize-path "^3.0.0"
+  optionalDependencies:
+    fsevents "^1.2.7"
+
+"fstream@>= 0.1.30 < 1", fstream@^0.1.31:
+  version "0.1.31"
+  resolved "https://registry.yarnpkg.com/fstream/-/fstream-0.1.31.tgz#7337f058fbbbbefa8c9f561a28cab0849202c988"
+  dependencies:
+    graceful-fs "~3.0.2"
+    inherits "~2.0.0"
+    mkdirp "0.5"
+    rimraf "2"
+
+normalize-package-data@^2.3.2, "normalize-package-data@~1.0.1 || ^2.0.0":
+  version "2.5.0"
+  resolved "https://registry.yarnpkg.com/normalize-package-data/-/normalize-package-data-2.5.0.tgz#e66db1838b200c1dfc233225d12cb36520e234a8"
+  dependencies:
+    hosted-git-info "^2.1.4"
+    resolve "^1.10.0"
+    semver "2 || 3 || 4 || 5"
+    validate-npm-package-license "^3.0.1"
+
+window-size@0.1.0:
+  version 0.1.0
+  resolved "https://registry.yarnpkg.com/window-size/-/window-size-0.1.0.tgz#5438cd2ea93b202efa3a19fe8887aee7c94f9c9d"
+"#;
+
+    /// Reads locks line by line without the lexer, grouping blocks by indentation like the old tokenizer.
+    fn reference_parse(content: &str) -> Vec<DependencyLock> {
+        let mut blocks: Vec<Vec<&str>> = Vec::new();
+        for line in content.lines() {
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            if line.starts_with(' ') {
+                blocks.last_mut().unwrap().push(line);
+            } else {
+                blocks.push(vec![line]);
+            }
+        }
+
+        let unquote = |s: &str| s.trim().trim_matches('"').to_string();
+        let mut locks = Vec::new();
+        for block in blocks {
+            let mut fields = HashMap::new();
+            let mut sections: HashMap<String, HashMap<String, Vec<VersionReq>>> = HashMap::new();
+            let mut section = String::new();
+            for line in &block[1..] {
+                // names never contain spaces, so the first one ends the key
+                let mut parts = line.trim().splitn(2, ' ');
+                let key = unquote(parts.next().unwrap());
+                let value = unquote(parts.next().unwrap_or(""));
+                if line.starts_with("    ") {
+                    sections.entry(section.clone()).or_default().insert(key, parse_reqs(&value).unwrap());
+                } else if let Some(name) = key.strip_suffix(':') {
+                    section = name.to_string();
+                } else {
+                    fields.insert(key, value);
+                }
+            }
+
+            for descriptor in block[0].trim_end_matches(':').split(", ").map(unquote) {
+                let at = descriptor[1..].find('@').unwrap() + 1;
+                locks.push(DependencyLock {
+                    name: descriptor[..at].to_string(),
+                    last_seen: VersionReq::parse(&descriptor[at + 1..]).ok(),
+                    version: fields.get("version").and_then(|v| Version::parse(v).ok()),
+                    resolved: fields.get("resolved").and_then(|r| Url::parse(r).ok()),
+                    integrity: fields.get("integrity").cloned(),
+                    dependencies: sections.get("dependencies").cloned().unwrap_or_default(),
+                    optional_dependencies: sections.get("optionalDependencies").cloned().unwrap_or_default(),
+                });
+            }
+        }
+        locks
+    }
+
+    #[test]
+    fn parses_like_the_line_based_reference() {
+        assert_eq!(parse(FIXTURE).unwrap(), reference_parse(FIXTURE));
+        assert_eq!(parse(test_file()).unwrap(), reference_parse(test_file()));
+        assert_eq!(parse(FIXTURE).unwrap().len(), 8);
     }
 
     #[test]
     fn parses_version_lines() {
-        assert_parser!(
-            version_line(r#"version 3.0.0"#),
-            Version::parse("3.0.0").unwrap()
-        );
-        assert_parser!(
-            version_line(r#"version "3.0.0""#),
-            Version::parse("3.0.0").unwrap()
-        );
+        for content in &["a@^1.0.0:\n  version 3.0.0\n", "a@^1.0.0:\n  version \"3.0.0\"\n"] {
+            assert_eq!(parse(content).unwrap()[0].version, Version::parse("3.0.0").ok());
+        }
     }
 
     #[test]
     fn parses_head_lines() {
-        assert_parser!(
-            headline_parts(r#""@protobufjs/aspromise@^1.1.1","@protobufjs/aspromise@^1.1.2""#),
-            vec![
-                (Some("^1.1.1"), Some("@protobufjs/aspromise")),
-                (Some("^1.1.2"), Some("@protobufjs/aspromise")),
-            ]
-        );
-
-        assert_parser!(
-            headline_parts(r#""@ava/babel-plugin-throws-helper@^2.0.0""#),
-            vec![(Some("^2.0.0"), Some("@ava/babel-plugin-throws-helper"))]
+        let descriptors = |headline: &str| {
+            parse_entries(&format!("{}:\n  version \"1.0.0\"\n", headline)).unwrap()[0].descriptors.clone()
+        };
+        assert_eq!(
+            descriptors(r#""@protobufjs/aspromise@^1.1.1","@protobufjs/aspromise@^1.1.2""#),
+            vec![Descriptor::new("@protobufjs/aspromise", "^1.1.1"), Descriptor::new("@protobufjs/aspromise", "^1.1.2")]
         );
-
-        assert_parser!(
-            headline_parts(r#""@ava/babe,-plugin-throws-helper@^2.0.0", "@ava/babel-plugin-throws-helper@^2.0.0""#),
-            vec![(Some("^2.0.0"), Some("@ava/babe,-plugin-throws-helper")),
-                 (Some("^2.0.0"), Some("@ava/babel-plugin-throws-helper"))]
+        assert_eq!(
+            descriptors(r#""@ava/babel-plugin-throws-helper@^2.0.0""#),
+            vec![Descriptor::new("@ava/babel-plugin-throws-helper", "^2.0.0")]
         );
-
-        assert_parser!(
-            headline_parts(r#"assertion-error@^1.0.1, assertion-error@^1.0.1"#),
+        assert_eq!(
+            descriptors(r#""@ava/babe,-plugin-throws-helper@^2.0.0", "@ava/babel-plugin-throws-helper@^2.0.0""#),
             vec![
-                (Some("^1.0.1"), Some("assertion-error")),
-                (Some("^1.0.1"), Some("assertion-error")),
+                Descriptor::new("@ava/babe,-plugin-throws-helper", "^2.0.0"),
+                Descriptor::new("@ava/babel-plugin-throws-helper", "^2.0.0"),
             ]
         );
-    }
-
-    #[test]
-    fn parses_head_lines_deep() {
-        assert_parser!(
-            headline_parts("fstream@>= 0.1.30 < 1"),
-            vec![
-                (
-                    Some(">= 0.1.30 < 1"),
-                    Some("fstream"),
-                )
-
-            ]);
+        assert_eq!(
+            descriptors("assertion-error@^1.0.1, assertion-error@^1.0.1"),
+            vec![Descriptor::new("assertion-error", "^1.0.1"), Descriptor::new("assertion-error", "^1.0.1")]
+        );
+        assert_eq!(descriptors(r#""fstream@>= 0.1.30 < 1""#), vec![Descriptor::new("fstream", ">= 0.1.30 < 1")]);
     }
 
     #[test]
     fn parses_dependency_lines() {
-        assert_parser!(
-            dependency_line(r#"version "1.4.0""#),
-            ("version", vec![VersionReq::parse("1.4.0").unwrap()])
-        );
-        assert_parser!(
-            dependency_line(r#"camelcase "^1.0.2""#),
-            ("camelcase", vec![VersionReq::parse("^1.0.2").unwrap()])
-        );
-        assert_parser!(
-            dependency_line(r#"cliui "^2.1.0""#),
-            ("cliui", vec![VersionReq::parse("^2.1.0").unwrap()])
-        );
-        assert_parser!(
-            dependency_line(r#"decamelize "^1.0.0""#),
-            ("decamelize", vec![VersionReq::parse("^1.0.0").unwrap()])
-        );
-        assert_parser!(
-            dependency_line(r#"window-size "0.1.0""#),
-            ("window-size", vec![VersionReq::parse("0.1.0").unwrap()])
-        );
-        assert_parser!(
-            dependency_line(r#""window-size" "0.1.0""#),
-            ("window-size", vec![VersionReq::parse("0.1.0").unwrap()])
-        );
+        let locks = parse(r#"yargs@^3.10.0:
+  version "3.10.0"
+  dependencies:
+    camelcase "^1.0.2"
+    cliui "^2.1.0"
+    decamelize "^1.0.0"
+    "window-size" "0.1.0"
+    through ">=2.2.7"
+"#).unwrap();
+        let reqs = |range: &str| vec![VersionReq::parse(range).unwrap()];
+        let dependencies = &locks[0].dependencies;
+        assert_eq!(dependencies.len(), 5);
+        assert_eq!(dependencies["camelcase"], reqs("^1.0.2"));
+        assert_eq!(dependencies["cliui"], reqs("^2.1.0"));
+        assert_eq!(dependencies["decamelize"], reqs("^1.0.0"));
+        // a plain version is exact in npm, unlike in `semver`
+        assert_eq!(dependencies["window-size"], reqs("=0.1.0"));
+        assert_eq!(dependencies["through"], reqs(">=2.2.7"));
     }
 
     #[test]
@@ -357,16 +294,61 @@ mod tests {
             r#"pullstream ">= 0.4.1 < 1""#,
             r#"setimmediate ">= 1.0.1 < 2""#,
         ];
-        let sample = samples[0];
-        assert_parser!(
-            dependency_line(r#"through ">=2.2.7""#),
-            ("through", vec![VersionReq::parse(">=2.2.7").unwrap()])
-        );
         for sample in &samples {
-            println!("{:?}", dependency_line(sample));
+            let (name, range) = split_key_value(sample).unwrap();
+            println!("{} {:?}", name, parse_reqs(range));
         }
     }
 
+    #[test]
+    fn parses_entries() {
+        let entries = parse_entries(test_file()).unwrap();
+        let first = &entries[0];
+        assert_eq!(
+            first.descriptors,
+            vec![Descriptor::new("@babel/code-frame", "^7.0.0"), Descriptor::new("@babel/code-frame", "^7.8.3")]
+        );
+        assert_eq!(first.version, Some("7.8.3".into()));
+        assert_eq!(first.dependencies.get("@babel/highlight"), Some(&"^7.8.3".to_string()));
+    }
+
+    #[test]
+    fn strips_the_colon_of_unquoted_headlines() {
+        let locks = parse("chalk@^2.0.0, chalk@^2.4.1:\n  version \"2.4.2\"\n").unwrap();
+        assert_eq!(
+            locks.iter().map(|lock| lock.last_seen.clone()).collect::<Vec<_>>(),
+            vec![VersionReq::parse("^2.0.0").ok(), VersionReq::parse("^2.4.1").ok()]
+        );
+    }
+
+    #[test]
+    fn splits_at_top_level_blocks() {
+        let chunks = split_blocks(test_file(), 8);
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.concat(), test_file());
+        assert!(chunks[1..].iter().all(|chunk| !chunk.starts_with(char::is_whitespace)));
+
+        let entries = chunks.iter().flat_map(|chunk| parse_entries(chunk).unwrap()).collect::<Vec<_>>();
+        assert_eq!(entries, parse_entries(test_file()).unwrap());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parses_in_parallel() {
+        let chunks = split_blocks(test_file(), 8);
+        assert!(chunks.len() > 1);
+        assert_eq!(parse_chunks(&chunks).unwrap(), parse_entries(test_file()).unwrap());
+        assert_eq!(parse_parallel(test_file()).unwrap(), parse(test_file()).unwrap());
+
+        let broken = format!("{}\nbroken@^1.0.0:\n   version \"1.0.0\"\n", test_file());
+        let chunks = split_blocks(&broken, 8);
+        assert!(chunks.len() > 1);
+        assert_eq!(
+            parse_chunks(&chunks).unwrap_err().to_string(),
+            parse_entries(&broken).unwrap_err().to_string()
+        );
+    }
+
     #[test]
     fn print() {
         let file = test_file();
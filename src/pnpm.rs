@@ -0,0 +1,287 @@
+//! Reading of pnpm's `pnpm-lock.yaml`.
+//!
+//! Supported are `lockfileVersion` 5.4, 6 and 9. Unlike yarn, pnpm records the resolved version of
+//! each transitive dependency instead of its range, so `dependencies` of the resulting locks
+//! contain exact requirements.
+
+use semver::{Version, VersionReq};
+use serde_json::{Map, Value};
+use serde_yaml;
+use url::Url;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ffi::OsStr;
+use std::path::Path;
+
+use super::{read_file, DependencyLock};
+use error;
+use manifest::Manifest;
+use npm_semver::parse_reqs;
+
+/// The content of a `pnpm-lock.yaml`.
+#[derive(Debug)]
+pub struct PnpmLock {
+    pub locks: Vec<DependencyLock>,
+    /// The requirements of each workspace package by path, `.` is the root.
+    pub importers: BTreeMap<String, Manifest>,
+}
+
+/// Splits a package key like `/@s/a/1.0.0_peer@1.0.0` (5.x), `/a@1.0.0(peer@1.0.0)` (6) or
+/// `a@1.0.0(peer@1.0.0)` (9) into name and version.
+fn split_package_key(key: &str, slash_separated: bool) -> Option<(&str, &str)> {
+    let key = key.trim_start_matches('/');
+    if slash_separated {
+        let name_segments = if key.starts_with('@') { 2 } else { 1 };
+        let split = key.match_indices('/').nth(name_segments - 1)?.0;
+        let version = key[split + 1..].split('_').next()?;
+        Some((&key[..split], version))
+    } else {
+        let key = key.split('(').next()?;
+        let split = key.rfind('@').filter(|&i| i > 0)?;
+        Some((&key[..split], &key[split + 1..]))
+    }
+}
+
+/// The version out of a dependency reference like `1.0.0_peer@2.0.0`, `1.0.0(peer@2.0.0)` or an
+/// alias like `npm:real@1.0.0`; links to local folders have none.
+fn reference_version(reference: &str) -> Option<&str> {
+    if reference.starts_with("link:") || reference.starts_with("file:") {
+        return None;
+    }
+    let reference = reference.split('(').next()?.split('_').next()?;
+    reference.rsplit(['@', '/']).next()
+}
+
+fn object<'v>(value: &'v Value, key: &str) -> Option<&'v Map<String, Value>> {
+    value.get(key).and_then(Value::as_object)
+}
+
+/// Default tarball location for packages that come from the registry.
+fn registry_tarball(name: &str, version: &str) -> Option<Url> {
+    let base_name = name.rsplit('/').next().unwrap_or(name);
+    Url::parse(&format!("https://registry.npmjs.org/{}/-/{}-{}.tgz", name, base_name, version)).ok()
+}
+
+fn group_mut<'m>(manifest: &'m mut Manifest, group: &str) -> &'m mut BTreeMap<String, String> {
+    match group {
+        "devDependencies" => &mut manifest.dev_dependencies,
+        "optionalDependencies" => &mut manifest.optional_dependencies,
+        _ => &mut manifest.dependencies,
+    }
+}
+
+/// Reads the requirements of one importer.
+///
+/// 5.x keeps the ranges in a separate `specifiers` map, 6 and 9 next to each resolved version.
+fn read_importer(importer: &Value) -> (Manifest, Vec<(String, String)>) {
+    let specifiers = object(importer, "specifiers");
+    let mut manifest = Manifest::default();
+    let mut resolved = Vec::new();
+
+    for group in &["dependencies", "devDependencies", "optionalDependencies"] {
+        for (name, entry) in object(importer, group).into_iter().flat_map(|deps| deps.iter()) {
+            let (specifier, version) = match *entry {
+                Value::String(ref version) => (
+                    specifiers.and_then(|s| s.get(name)).and_then(Value::as_str),
+                    version.as_str(),
+                ),
+                _ => (
+                    entry.get("specifier").and_then(Value::as_str),
+                    entry.get("version").and_then(Value::as_str).unwrap_or_default(),
+                ),
+            };
+            if let Some(specifier) = specifier {
+                group_mut(&mut manifest, group).insert(name.clone(), specifier.to_string());
+                if let Some(version) = reference_version(version) {
+                    resolved.push((format!("{}@{}", name, version), specifier.to_string()));
+                }
+            }
+        }
+    }
+    (manifest, resolved)
+}
+
+/// Resolved versions of a package entry or snapshot, by dependency name.
+fn read_dependency_versions(entry: &Value) -> HashMap<String, Vec<VersionReq>> {
+    ["dependencies", "optionalDependencies"]
+        .iter()
+        .filter_map(|group| object(entry, group))
+        .flat_map(|deps| deps.iter())
+        .filter_map(|(name, reference)| {
+            let version = reference.as_str().and_then(reference_version)?;
+            parse_reqs(version).map(|reqs| (name.clone(), reqs))
+        })
+        .collect()
+}
+
+/// Parses the content of a `pnpm-lock.yaml`.
+pub fn parse_pnpm_lock(content: &str) -> Result<PnpmLock, error::Error> {
+    let value: Value = serde_yaml::from_str(content)?;
+
+    let lockfile_version = match value.get("lockfileVersion") {
+        Some(Value::String(version)) => version.parse::<f64>().ok(),
+        Some(version) => version.as_f64(),
+        None => None,
+    };
+    let lockfile_version = match lockfile_version {
+        Some(version) if (5.3..10.0).contains(&version) => version,
+        other => return Err(error::Error::UnsupportedFormat(format!("pnpm-lock.yaml version {:?}", other))),
+    };
+    let slash_separated = lockfile_version < 6.0;
+
+    // lockfiles without workspaces keep the root importer at the top level
+    let mut importer_values = object(&value, "importers")
+        .map(|importers| importers.iter().map(|(path, importer)| (path.clone(), importer)).collect::<Vec<_>>())
+        .unwrap_or_default();
+    if importer_values.is_empty() {
+        importer_values.push((".".into(), &value));
+    }
+
+    let mut importers = BTreeMap::new();
+    let mut specifiers: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for (path, importer) in importer_values {
+        let (manifest, resolved) = read_importer(importer);
+        for (id, specifier) in resolved {
+            specifiers.entry(id).or_default().insert(specifier);
+        }
+        importers.insert(path, manifest);
+    }
+
+    // since 9 the dependencies of a package are moved out into `snapshots`
+    let packages = object(&value, "packages").cloned().unwrap_or_default();
+    let snapshots = object(&value, "snapshots").cloned().unwrap_or_else(|| packages.clone());
+
+    let mut seen = BTreeSet::new();
+    let mut locks = Vec::new();
+    for (key, snapshot) in &snapshots {
+        let (name, version) = match split_package_key(key, slash_separated) {
+            Some(split) => split,
+            None => {
+                warn!("INVALID package key {:?}", key);
+                continue;
+            }
+        };
+        let id = format!("{}@{}", name, version);
+        if !seen.insert(id.clone()) {
+            continue;
+        }
+
+        let package = packages
+            .get(key)
+            .or_else(|| packages.get(&id))
+            .unwrap_or(snapshot);
+        let resolution = package.get("resolution");
+
+        let lock = DependencyLock {
+            name:         name.to_string(),
+            last_seen:    None,
+            version:      Version::parse(version).ok(),
+            resolved:     resolution
+                .and_then(|r| r.get("tarball"))
+                .and_then(Value::as_str)
+                .and_then(|tarball| Url::parse(tarball).ok())
+                .or_else(|| registry_tarball(name, version)),
+            integrity:    resolution
+                .and_then(|r| r.get("integrity"))
+                .and_then(Value::as_str)
+                .map(ToString::to_string),
+            dependencies: read_dependency_versions(snapshot),
+            optional_dependencies: HashMap::new(),
+        };
+
+        // one lock per range the importers asked for, like the headlines of a `yarn.lock`
+        let last_seen = specifiers
+            .get(&id)
+            .map(|specifiers| specifiers.iter().map(|s| VersionReq::parse(s).ok()).collect::<Vec<_>>())
+            .unwrap_or_else(|| vec![VersionReq::parse(&format!("={}", version)).ok()]);
+        locks.extend(last_seen.into_iter().map(|last_seen| DependencyLock {
+            last_seen,
+            name: lock.name.clone(),
+            version: lock.version.clone(),
+            resolved: lock.resolved.clone(),
+            integrity: lock.integrity.clone(),
+            dependencies: lock.dependencies.clone(),
+            optional_dependencies: lock.optional_dependencies.clone(),
+        }));
+    }
+
+    Ok(PnpmLock { locks, importers })
+}
+
+/// Opens a given `pnpm-lock.yaml` or the one inside a folder.
+pub fn open_pnpm_lock<P: AsRef<OsStr> + Sized>(given_path: P) -> Result<PnpmLock, error::Error> {
+    let path = Path::new(&given_path);
+    read_file(path, "pnpm-lock.yaml").and_then(|s| parse_pnpm_lock(&s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_package_keys() {
+        assert_eq!(split_package_key("/@s/a/1.0.0_peer@1.0.0", true), Some(("@s/a", "1.0.0")));
+        assert_eq!(split_package_key("/a/1.0.0", true), Some(("a", "1.0.0")));
+        assert_eq!(split_package_key("/@s/a@1.0.0(peer@1.0.0)", false), Some(("@s/a", "1.0.0")));
+        assert_eq!(split_package_key("a@1.0.0", false), Some(("a", "1.0.0")));
+        assert_eq!(reference_version("npm:real@2.0.0"), Some("2.0.0"));
+        assert_eq!(reference_version("1.0.0(peer@1.0.0)"), Some("1.0.0"));
+        assert_eq!(reference_version("link:../b"), None);
+    }
+
+    #[test]
+    fn parses_v5() {
+        let lock = parse_pnpm_lock(r#"
+lockfileVersion: 5.4
+specifiers:
+  a: ^1.0.0
+dependencies:
+  a: 1.0.0
+packages:
+  /a/1.0.0:
+    resolution: {integrity: sha512-aaa}
+    dependencies:
+      c: 2.0.0_peer@1.0.0
+  /c/2.0.0_peer@1.0.0:
+    resolution: {integrity: sha512-ccc}
+"#).unwrap();
+
+        assert_eq!(lock.importers["."].dependencies["a"], "^1.0.0");
+        assert_eq!(lock.locks.len(), 2);
+        assert_eq!(lock.locks[0].last_seen, VersionReq::parse("^1.0.0").ok());
+        assert_eq!(lock.locks[0].dependencies["c"], parse_reqs("2.0.0").unwrap());
+        assert_eq!(lock.locks[1].integrity, Some("sha512-ccc".into()));
+    }
+
+    #[test]
+    fn parses_v9() {
+        let lock = parse_pnpm_lock(r#"
+lockfileVersion: '9.0'
+importers:
+  .:
+    devDependencies:
+      '@s/a':
+        specifier: ~1.0.0
+        version: 1.0.1
+packages:
+  '@s/a@1.0.1':
+    resolution: {integrity: sha512-aaa}
+  c@2.0.0:
+    resolution: {integrity: sha512-ccc, tarball: https://example.com/c.tgz}
+snapshots:
+  '@s/a@1.0.1':
+    dependencies:
+      c: 2.0.0
+  c@2.0.0: {}
+"#).unwrap();
+
+        assert_eq!(lock.importers["."].dev_dependencies["@s/a"], "~1.0.0");
+        assert_eq!(lock.locks[0].name, "@s/a");
+        assert_eq!(lock.locks[0].integrity, Some("sha512-aaa".into()));
+        assert_eq!(
+            lock.locks[0].resolved.as_ref().map(Url::as_str),
+            Some("https://registry.npmjs.org/@s/a/-/a-1.0.1.tgz")
+        );
+        assert_eq!(lock.locks[1].resolved.as_ref().map(Url::as_str), Some("https://example.com/c.tgz"));
+    }
+}
@@ -0,0 +1,366 @@
+//! Registry metadata from a local cache, and resolving descriptors against it.
+//!
+//! The cache is a folder of packuments, the documents the registry serves at `/<name>`, saved as
+//! `<name>.json`. Scoped packages live in a `@scope/` subfolder or in a file named `@scope%2fname.json`.
+
+use semver::Version;
+use serde_json::{self, Map, Value};
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use entry::LockEntry;
+use error::Error;
+use npm_semver::parse_reqs;
+
+/// One published version of a package.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageVersion {
+    pub version:               Version,
+    pub dependencies:          BTreeMap<String, String>,
+    pub optional_dependencies: BTreeMap<String, String>,
+    /// `dist.tarball`
+    pub tarball:               Option<String>,
+    /// `dist.integrity`
+    pub integrity:             Option<String>,
+    /// `dist.shasum`, the hex sha1 of the tarball
+    pub shasum:                Option<String>,
+}
+
+/// Everything the registry knows about a package.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Packument {
+    pub name:      String,
+    /// Versions that are not valid semver are left out.
+    pub versions:  BTreeMap<Version, PackageVersion>,
+    pub dist_tags: BTreeMap<String, String>,
+}
+
+fn string_map(object: &Map<String, Value>, key: &str) -> BTreeMap<String, String> {
+    object
+        .get(key)
+        .and_then(Value::as_object)
+        .map(|map| {
+            map.iter()
+                .filter_map(|(key, value)| value.as_str().map(|value| (key.clone(), value.to_string())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl PackageVersion {
+    fn from_json(version: Version, object: &Map<String, Value>) -> PackageVersion {
+        let dist = object.get("dist").and_then(Value::as_object);
+        let dist_field = |key| dist.and_then(|dist| dist.get(key)).and_then(Value::as_str).map(ToString::to_string);
+        PackageVersion {
+            version,
+            dependencies: string_map(object, "dependencies"),
+            optional_dependencies: string_map(object, "optionalDependencies"),
+            tarball: dist_field("tarball"),
+            integrity: dist_field("integrity"),
+            shasum: dist_field("shasum"),
+        }
+    }
+
+    /// The `resolved` field yarn writes, the tarball url with the sha1 as fragment.
+    pub fn resolved(&self) -> Option<String> {
+        self.tarball.as_ref().map(|tarball| match self.shasum {
+            Some(ref shasum) => format!("{}#{}", tarball, shasum),
+            None => tarball.clone(),
+        })
+    }
+
+    /// A lock entry for this version, without descriptors.
+    pub fn to_entry(&self) -> LockEntry {
+        LockEntry {
+            descriptors:           Vec::new(),
+            version:               Some(self.version.to_string()),
+            resolved:              self.resolved(),
+            integrity:             self.integrity.clone(),
+            dependencies:          self.dependencies.clone(),
+            optional_dependencies: self.optional_dependencies.clone(),
+        }
+    }
+}
+
+impl Packument {
+    /// Parses a packument, full or abbreviated.
+    pub fn parse(content: &str) -> Result<Packument, Error> {
+        let value: Value = serde_json::from_str(content)?;
+        let object = value.as_object().ok_or(Error::InvalidPackument)?;
+        let name = object.get("name").and_then(Value::as_str).ok_or(Error::InvalidPackument)?;
+        let versions = object
+            .get("versions")
+            .and_then(Value::as_object)
+            .ok_or(Error::InvalidPackument)?
+            .iter()
+            .filter_map(|(version, metadata)| {
+                let version = Version::parse(version).ok()?;
+                Some((version.clone(), PackageVersion::from_json(version, metadata.as_object()?)))
+            })
+            .collect();
+
+        Ok(Packument { name: name.to_string(), versions, dist_tags: string_map(object, "dist-tags") })
+    }
+
+    fn tagged(&self, tag: &str) -> Option<&PackageVersion> {
+        let version = Version::parse(self.dist_tags.get(tag)?).ok()?;
+        self.versions.get(&version)
+    }
+
+    /// The version tagged `latest`, or the highest release if there is no such tag.
+    pub fn latest(&self) -> Option<&PackageVersion> {
+        self.tagged("latest").or_else(|| {
+            self.versions
+                .values()
+                .rev()
+                .find(|version| !version.version.is_prerelease())
+        })
+    }
+
+    /// The version yarn would pick for `range`.
+    ///
+    /// A range can name a dist-tag, the `latest` version wins if it satisfies the range, otherwise the
+    /// highest satisfying version is used.
+    pub fn max_satisfying(&self, range: &str) -> Option<&PackageVersion> {
+        if let Some(tagged) = self.tagged(range) {
+            return Some(tagged);
+        }
+        let range = if range.is_empty() { "*" } else { range };
+        let reqs = if range == "*" { Vec::new() } else { parse_reqs(range)? };
+        let satisfies = |found: &&PackageVersion| {
+            if reqs.is_empty() {
+                !found.version.is_prerelease()
+            } else {
+                reqs.iter().any(|req| req.matches(&found.version))
+            }
+        };
+
+        self.tagged("latest")
+            .filter(satisfies)
+            .or_else(|| self.versions.values().rev().find(satisfies))
+    }
+}
+
+/// Lazily loaded packuments of a cache folder.
+#[derive(Debug)]
+pub struct PackumentCache {
+    dir:        PathBuf,
+    packuments: HashMap<String, Option<Packument>>,
+}
+
+impl PackumentCache {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> PackumentCache {
+        PackumentCache { dir: dir.into(), packuments: HashMap::new() }
+    }
+
+    /// Adds a packument without reading it from the folder.
+    pub fn insert(&mut self, packument: Packument) {
+        self.packuments.insert(packument.name.clone(), Some(packument));
+    }
+
+    fn read(&self, name: &str) -> Result<Option<Packument>, Error> {
+        let candidates = [
+            self.dir.join(format!("{}.json", name)),
+            self.dir.join(format!("{}.json", name.replace('/', "%2f"))),
+        ];
+        for path in &candidates {
+            let mut content = String::new();
+            match File::open(path).and_then(|mut file| file.read_to_string(&mut content)) {
+                Ok(_) => {
+                    debug!("read packument {:?}", path);
+                    return Packument::parse(&content).map(Some);
+                }
+                Err(ref error) if error.kind() == ErrorKind::NotFound => (),
+                Err(error) => return Err(Error::io(path)(error)),
+            }
+        }
+        Ok(None)
+    }
+
+    /// The packument of `name`, `None` if it is not cached.
+    pub fn get(&mut self, name: &str) -> Result<Option<&Packument>, Error> {
+        if !self.packuments.contains_key(name) {
+            let packument = self.read(name)?;
+            self.packuments.insert(name.to_string(), packument);
+        }
+        Ok(self.packuments.get(name).and_then(Option::as_ref))
+    }
+
+    /// The version yarn would pick for `name@range`, see `Packument::max_satisfying`.
+    pub fn resolve(&mut self, name: &str, range: &str) -> Result<Option<&PackageVersion>, Error> {
+        Ok(self.get(name)?.and_then(|packument| packument.max_satisfying(range)))
+    }
+}
+
+/// A descriptor whose locked version is behind, like a line of `yarn outdated`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outdated {
+    pub name:    String,
+    pub range:   String,
+    pub current: Version,
+    /// The highest version the range allows, `None` if nothing satisfies it.
+    pub wanted:  Option<Version>,
+    pub latest:  Option<Version>,
+}
+
+impl Outdated {
+    /// Whether a newer version is allowed by the range.
+    pub fn in_range(&self) -> bool {
+        self.wanted.as_ref().is_some_and(|wanted| *wanted > self.current)
+    }
+
+    /// Whether there is a newer major version.
+    pub fn is_major(&self) -> bool {
+        self.latest.as_ref().is_some_and(|latest| latest.major > self.current.major)
+    }
+}
+
+impl fmt::Display for Outdated {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let show = |version: &Option<Version>| version.as_ref().map(ToString::to_string).unwrap_or_else(|| "-".into());
+        write!(
+            f,
+            "{}@{}: {} wanted {} latest {}",
+            self.name,
+            self.range,
+            self.current,
+            show(&self.wanted),
+            show(&self.latest)
+        )?;
+        if self.is_major() {
+            write!(f, " (major)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Lists the descriptors of `entries` that are behind the cached registry metadata, sorted by name.
+///
+/// Packages without a cached packument are skipped.
+pub fn outdated(entries: &[LockEntry], cache: &mut PackumentCache) -> Result<Vec<Outdated>, Error> {
+    let mut outdated = Vec::new();
+    for entry in entries {
+        let current = match entry.parsed_version() {
+            Some(version) => version,
+            None => continue,
+        };
+        for descriptor in &entry.descriptors {
+            let packument = match cache.get(&descriptor.name)? {
+                Some(packument) => packument,
+                None => continue,
+            };
+            let report = Outdated {
+                name:    descriptor.name.clone(),
+                range:   descriptor.range.clone(),
+                current: current.clone(),
+                wanted:  packument.max_satisfying(&descriptor.range).map(|found| found.version.clone()),
+                latest:  packument.latest().map(|latest| latest.version.clone()),
+            };
+            if report.in_range() || report.latest.as_ref().is_some_and(|latest| *latest > current) {
+                outdated.push(report);
+            }
+        }
+    }
+    outdated.sort_by(|a, b| (&a.name, &a.range).cmp(&(&b.name, &b.range)));
+    Ok(outdated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use entry::Descriptor;
+
+    const LEFT_PAD: &str = r#"{
+        "name": "left-pad",
+        "dist-tags": { "latest": "1.3.0", "next": "2.0.0-beta.1" },
+        "versions": {
+            "1.1.0": { "dist": { "tarball": "https://registry.yarnpkg.com/left-pad/-/left-pad-1.1.0.tgz" } },
+            "1.3.0": {
+                "dependencies": { "repeat": "^1.0.0" },
+                "dist": {
+                    "tarball": "https://registry.yarnpkg.com/left-pad/-/left-pad-1.3.0.tgz",
+                    "shasum": "5b8a3a7765dfe00126",
+                    "integrity": "sha512-x"
+                }
+            },
+            "1.4.0": {},
+            "2.0.0-beta.1": {},
+            "invalid": {}
+        }
+    }"#;
+
+    fn version(version: &str) -> Version {
+        Version::parse(version).unwrap()
+    }
+
+    #[test]
+    fn parses_packuments() {
+        let packument = Packument::parse(LEFT_PAD).unwrap();
+        assert_eq!(packument.versions.len(), 4);
+        let latest = packument.latest().unwrap();
+        assert_eq!(latest.version, version("1.3.0"));
+        assert_eq!(latest.integrity, Some("sha512-x".into()));
+        assert_eq!(latest.dependencies.get("repeat"), Some(&"^1.0.0".to_string()));
+        assert_eq!(
+            latest.resolved(),
+            Some("https://registry.yarnpkg.com/left-pad/-/left-pad-1.3.0.tgz#5b8a3a7765dfe00126".into())
+        );
+        assert!(Packument::parse("[]").is_err());
+    }
+
+    #[test]
+    fn picks_versions_like_yarn() {
+        let packument = Packument::parse(LEFT_PAD).unwrap();
+        let pick = |range| packument.max_satisfying(range).map(|found| found.version.to_string());
+        assert_eq!(pick("^1.0.0"), Some("1.3.0".into()));
+        assert_eq!(pick(">=1.3.1"), Some("1.4.0".into()));
+        assert_eq!(pick("~1.1.0"), Some("1.1.0".into()));
+        assert_eq!(pick("next"), Some("2.0.0-beta.1".into()));
+        assert_eq!(pick("*"), Some("1.3.0".into()));
+        assert_eq!(pick("^3.0.0"), None);
+    }
+
+    #[test]
+    fn reports_outdated_entries() {
+        let mut cache = PackumentCache::new("/nonexistent");
+        cache.insert(Packument::parse(LEFT_PAD).unwrap());
+        let entry = |descriptors: Vec<Descriptor>, version: &str| LockEntry {
+            descriptors,
+            version: Some(version.into()),
+            ..LockEntry::default()
+        };
+        let entries = vec![
+            entry(vec![Descriptor::new("left-pad", "~1.1.0"), Descriptor::new("left-pad", "^1.1.0")], "1.1.0"),
+            entry(vec![Descriptor::new("left-pad", "1.4.0")], "1.4.0"),
+            entry(vec![Descriptor::new("unknown", "^1.0.0")], "1.0.0"),
+        ];
+
+        let outdated = outdated(&entries, &mut cache).unwrap();
+        assert_eq!(
+            outdated,
+            vec![
+                Outdated {
+                    name:    "left-pad".into(),
+                    range:   "^1.1.0".into(),
+                    current: version("1.1.0"),
+                    wanted:  Some(version("1.3.0")),
+                    latest:  Some(version("1.3.0")),
+                },
+                Outdated {
+                    name:    "left-pad".into(),
+                    range:   "~1.1.0".into(),
+                    current: version("1.1.0"),
+                    wanted:  Some(version("1.1.0")),
+                    latest:  Some(version("1.3.0")),
+                },
+            ]
+        );
+        assert!(outdated[0].in_range() && !outdated[1].in_range());
+        assert!(!outdated[0].is_major());
+    }
+}
@@ -0,0 +1,221 @@
+//! Draws the dependency graph of the locks as Graphviz DOT or Mermaid.
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt::Write;
+
+use super::DependencyLock;
+use graph::{lock_id, Graph};
+
+/// What to draw.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Only the packages reachable from every locked version of this package
+    pub root:       Option<String>,
+    /// How many edges to follow from `root`, or from the packages nothing depends on
+    pub depth:      Option<usize>,
+    /// One node per package name instead of one per `name@version`
+    pub collapse:   bool,
+    /// Mark packages that are locked in several versions
+    pub duplicates: bool,
+}
+
+/// The selected part of the graph, ready to be written out.
+#[derive(Debug, Default, PartialEq)]
+struct Drawing {
+    /// Labels, with whether the package is locked in several versions
+    nodes: BTreeMap<String, bool>,
+    /// From and to, with whether the dependency is optional
+    edges: BTreeMap<(String, String), bool>,
+}
+
+impl Drawing {
+    fn new(graph: &Graph, options: &Options) -> Drawing {
+        let nodes = graph.nodes();
+        let locks = match options.root {
+            Some(ref root) => reachable(graph, graph.versions(root).to_vec(), options.depth),
+            None if options.depth.is_some() => {
+                let depended_on = nodes
+                    .iter()
+                    .flat_map(|lock| graph.dependencies_of(lock))
+                    .map(lock_id)
+                    .collect::<HashSet<_>>();
+                let roots = nodes.iter().cloned().filter(|lock| !depended_on.contains(&lock_id(lock))).collect();
+                reachable(graph, roots, options.depth)
+            }
+            None => nodes.into_iter().map(|lock| (lock_id(lock), (lock, true))).collect(),
+        };
+
+        let label = |lock: &DependencyLock| if options.collapse { lock.name.clone() } else { lock_id(lock) };
+        let mut drawing = Drawing::default();
+        for &(lock, expanded) in locks.values() {
+            let duplicate = options.duplicates && graph.versions(&lock.name).len() > 1;
+            drawing.nodes.insert(label(lock), duplicate);
+            if !expanded {
+                continue;
+            }
+            for (dependency, optional) in graph.edges_of(lock) {
+                let edge = (label(lock), label(dependency));
+                // versions of one package depending on each other don't mean anything once collapsed
+                if locks.contains_key(&lock_id(dependency)) && edge.0 != edge.1 {
+                    // collapsed edges are only optional if all of them are
+                    *drawing.edges.entry(edge).or_insert(optional) &= optional;
+                }
+            }
+        }
+        drawing
+    }
+}
+
+/// Locks up to `depth` edges away from `roots` by id, with whether their edges are drawn.
+fn reachable<'a>(
+    graph: &Graph<'a>,
+    roots: Vec<&'a DependencyLock>,
+    depth: Option<usize>,
+) -> HashMap<String, (&'a DependencyLock, bool)> {
+    let mut found = HashMap::new();
+    let mut queue = roots.into_iter().map(|lock| (lock, 0)).collect::<VecDeque<_>>();
+    while let Some((lock, distance)) = queue.pop_front() {
+        let id = lock_id(lock);
+        if found.contains_key(&id) {
+            continue;
+        }
+        let expanded = depth.is_none_or(|depth| distance < depth);
+        found.insert(id, (lock, expanded));
+        if expanded {
+            queue.extend(graph.dependencies_of(lock).into_iter().map(|dependency| (dependency, distance + 1)));
+        }
+    }
+    found
+}
+
+/// Writes the graph in the Graphviz DOT language, e.g. for `dot -Tsvg`.
+pub fn to_dot(graph: &Graph, options: &Options) -> String {
+    let drawing = Drawing::new(graph, options);
+    let mut dot = String::from("digraph dependencies {\n");
+    for (label, &duplicate) in &drawing.nodes {
+        if duplicate {
+            writeln!(dot, "  {:?} [style=filled, fillcolor=\"#f8d7da\"];", label).unwrap();
+        } else {
+            writeln!(dot, "  {:?};", label).unwrap();
+        }
+    }
+    for ((from, to), &optional) in &drawing.edges {
+        if optional {
+            writeln!(dot, "  {:?} -> {:?} [style=dashed];", from, to).unwrap();
+        } else {
+            writeln!(dot, "  {:?} -> {:?};", from, to).unwrap();
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// Writes the graph as a Mermaid flowchart, e.g. for Markdown on GitHub.
+pub fn to_mermaid(graph: &Graph, options: &Options) -> String {
+    let drawing = Drawing::new(graph, options);
+    // labels like `@babel/core@7.0.0` can't be node ids
+    let ids = drawing.nodes.keys().enumerate().map(|(i, label)| (label, format!("n{}", i))).collect::<HashMap<_, _>>();
+
+    let mut mermaid = String::from("graph LR\n");
+    for label in drawing.nodes.keys() {
+        writeln!(mermaid, "  {}[\"{}\"]", ids[label], label).unwrap();
+    }
+    for ((from, to), &optional) in &drawing.edges {
+        let arrow = if optional { "-.->" } else { "-->" };
+        writeln!(mermaid, "  {} {} {}", ids[from], arrow, ids[to]).unwrap();
+    }
+    let duplicates = drawing.nodes
+        .iter()
+        .filter(|&(_, &duplicate)| duplicate)
+        .map(|(label, _)| ids[label].as_str())
+        .collect::<Vec<_>>();
+    if !duplicates.is_empty() {
+        mermaid.push_str("  classDef duplicate fill:#f8d7da,stroke:#c00\n");
+        writeln!(mermaid, "  class {} duplicate", duplicates.join(",")).unwrap();
+    }
+    mermaid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse;
+
+    const LOCKFILE: &str = r#"
+a@^1.0.0:
+  version "1.0.0"
+  dependencies:
+    b "^1.0.0"
+    c "^1.0.0"
+
+b@^1.0.0:
+  version "1.0.0"
+  dependencies:
+    c "^2.0.0"
+
+c@^1.0.0:
+  version "1.1.0"
+
+c@^2.0.0:
+  version "2.0.0"
+"#;
+
+    #[test]
+    fn draws_dot() {
+        let locks = parse(LOCKFILE).unwrap();
+        let graph = Graph::new(&locks);
+
+        let options = Options { duplicates: true, ..Options::default() };
+        assert_eq!(to_dot(&graph, &options), r##"digraph dependencies {
+  "a@1.0.0";
+  "b@1.0.0";
+  "c@1.1.0" [style=filled, fillcolor="#f8d7da"];
+  "c@2.0.0" [style=filled, fillcolor="#f8d7da"];
+  "a@1.0.0" -> "b@1.0.0";
+  "a@1.0.0" -> "c@1.1.0";
+  "b@1.0.0" -> "c@2.0.0";
+}
+"##);
+
+        let options = Options { root: Some("b".into()), collapse: true, ..Options::default() };
+        assert_eq!(to_dot(&graph, &options), "digraph dependencies {\n  \"b\";\n  \"c\";\n  \"b\" -> \"c\";\n}\n");
+    }
+
+    #[test]
+    fn draws_mermaid() {
+        let locks = parse(LOCKFILE).unwrap();
+        let graph = Graph::new(&locks);
+
+        let options = Options { depth: Some(1), collapse: true, duplicates: true, ..Options::default() };
+        assert_eq!(to_mermaid(&graph, &options), r#"graph LR
+  n0["a"]
+  n1["b"]
+  n2["c"]
+  n0 --> n1
+  n0 --> n2
+  classDef duplicate fill:#f8d7da,stroke:#c00
+  class n2 duplicate
+"#);
+    }
+
+    #[test]
+    fn dashes_optional_edges() {
+        let locks = parse(r#"
+chokidar@^2.0.0:
+  version "2.1.8"
+  optionalDependencies:
+    fsevents "^1.2.7"
+
+fsevents@^1.2.7:
+  version "1.2.7"
+"#).unwrap();
+        let graph = Graph::new(&locks);
+
+        let options = Options { collapse: true, ..Options::default() };
+        assert_eq!(
+            to_dot(&graph, &options),
+            "digraph dependencies {\n  \"chokidar\";\n  \"fsevents\";\n  \"chokidar\" -> \"fsevents\" [style=dashed];\n}\n"
+        );
+        assert_eq!(to_mermaid(&graph, &options), "graph LR\n  n0[\"chokidar\"]\n  n1[\"fsevents\"]\n  n0 -.-> n1\n");
+    }
+}
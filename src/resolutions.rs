@@ -0,0 +1,319 @@
+//! Checks the `resolutions` of a `package.json` against the locks.
+//!
+//! A resolution like `"a/**/lodash": "4.17.21"` forces every `lodash` below `a` to one version.
+//! Patterns are matched against dependency paths, the names of the packages from a direct
+//! dependency down to the package itself. A plain name like `lodash` means `**/lodash`.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+use std::fmt;
+
+use super::DependencyLock;
+use error::Error;
+use graph::{lock_id, Graph};
+use manifest::Manifest;
+use npm_semver::parse_reqs;
+
+/// One part of a resolution pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Name(String),
+    /// `**`, any number of packages
+    Globstar,
+}
+
+/// A parsed entry of `resolutions`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolution {
+    /// As written in the `package.json`
+    pub pattern:  String,
+    pub segments: Vec<Segment>,
+    pub range:    String,
+}
+
+/// Positions in `segments` a partial path can have reached.
+type States = BTreeSet<usize>;
+
+fn parse_segments(pattern: &str) -> Result<Vec<Segment>, &'static str> {
+    let mut segments = Vec::new();
+    let mut parts = pattern.split('/');
+    while let Some(part) = parts.next() {
+        segments.push(match part {
+            "" => return Err("empty path segment"),
+            "**" => Segment::Globstar,
+            _ if part.contains('*') => return Err("only `**` is supported as glob"),
+            _ if part.starts_with('@') => match parts.next() {
+                Some(name) if !name.is_empty() && !name.contains('*') => Segment::Name(format!("{}/{}", part, name)),
+                _ => return Err("scope without a package name"),
+            },
+            _ => Segment::Name(part.to_string()),
+        });
+    }
+    match segments.last() {
+        Some(&Segment::Name(_)) => (),
+        _ => return Err("the pattern has to end with a package name"),
+    }
+    if segments.len() == 1 {
+        segments.insert(0, Segment::Globstar);
+    }
+    Ok(segments)
+}
+
+impl Resolution {
+    pub fn parse(pattern: &str, range: &str) -> Result<Resolution, Error> {
+        match parse_segments(pattern) {
+            Ok(segments) => Ok(Resolution { pattern: pattern.to_string(), segments, range: range.to_string() }),
+            Err(reason) => Err(Error::InvalidResolution { pattern: pattern.to_string(), reason }),
+        }
+    }
+
+    /// Every pattern matches the empty path up to its leading globstars.
+    fn start(&self) -> States {
+        self.skip_globstars(Some(0).into_iter().collect())
+    }
+
+    fn skip_globstars(&self, mut states: States) -> States {
+        let mut pending = states.iter().cloned().collect::<Vec<_>>();
+        while let Some(state) = pending.pop() {
+            if let Some(&Segment::Globstar) = self.segments.get(state) {
+                if states.insert(state + 1) {
+                    pending.push(state + 1);
+                }
+            }
+        }
+        states
+    }
+
+    /// The states after appending the package `name` to the path.
+    fn advance(&self, states: &States, name: &str) -> States {
+        let next = states
+            .iter()
+            .filter_map(|&state| match self.segments.get(state) {
+                Some(&Segment::Globstar) => Some(state),
+                Some(Segment::Name(segment)) if segment == name => Some(state + 1),
+                _ => None,
+            })
+            .collect();
+        self.skip_globstars(next)
+    }
+
+    fn accepts(&self, states: &States) -> bool {
+        states.contains(&self.segments.len())
+    }
+
+    /// Whether the dependency path, from a direct dependency down, matches the pattern.
+    pub fn matches(&self, path: &[&str]) -> bool {
+        let states = path.iter().fold(self.start(), |states, name| self.advance(&states, name));
+        self.accepts(&states)
+    }
+}
+
+/// A lock a resolution applies to, with the first dependency path that leads to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target<'a> {
+    pub pattern: String,
+    pub path:    Vec<String>,
+    pub lock:    &'a DependencyLock,
+}
+
+/// Why the locks don't honour the `resolutions`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// The pattern can't be used, yarn ignores it too.
+    Invalid { pattern: String, reason: &'static str },
+    /// No dependency path matches the pattern.
+    Unused { pattern: String },
+    /// A matching package is locked in a version the resolution doesn't allow.
+    Violated { pattern: String, range: String, path: Vec<String>, found: String },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Problem::Invalid { ref pattern, reason } => write!(f, "{}: {}", pattern, reason),
+            Problem::Unused { ref pattern } => write!(f, "{}: matches no locked package", pattern),
+            Problem::Violated { ref pattern, ref range, ref path, ref found } => {
+                write!(f, "{}: {} is locked as {}, not {}", pattern, path.join("/"), found, range)
+            }
+        }
+    }
+}
+
+/// Where the `resolutions` apply and what is wrong with them.
+#[derive(Debug, Default)]
+pub struct Report<'a> {
+    pub targets:  Vec<Target<'a>>,
+    pub problems: Vec<Problem>,
+}
+
+/// Matches the `resolutions` of `manifest` against every dependency path of the graph.
+pub fn check<'a>(manifest: &Manifest, graph: &Graph<'a>) -> Report<'a> {
+    let mut report = Report::default();
+    let mut resolutions = Vec::new();
+    for (pattern, range) in &manifest.resolutions {
+        match parse_segments(pattern) {
+            Ok(segments) => resolutions.push(Resolution { pattern: pattern.clone(), segments, range: range.clone() }),
+            Err(reason) => report.problems.push(Problem::Invalid { pattern: pattern.clone(), reason }),
+        }
+    }
+
+    // breadth first, so every target is reported with a shortest path
+    let start = resolutions.iter().map(Resolution::start).collect::<Vec<_>>();
+    let mut queue = manifest
+        .dependencies
+        .iter()
+        .chain(&manifest.dev_dependencies)
+        .chain(&manifest.optional_dependencies)
+        .filter_map(|(name, range)| graph.resolve_range(name, range))
+        .map(|lock| (lock, Vec::new(), start.clone()))
+        .collect::<VecDeque<_>>();
+    let mut seen = HashSet::new();
+    let mut targets: BTreeMap<(usize, String), Target<'a>> = BTreeMap::new();
+
+    while let Some((lock, mut path, states)) = queue.pop_front() {
+        let states = resolutions
+            .iter()
+            .zip(&states)
+            .map(|(resolution, states)| resolution.advance(states, &lock.name))
+            .collect::<Vec<_>>();
+        if !seen.insert((lock_id(lock), states.clone())) {
+            continue;
+        }
+        path.push(lock.name.clone());
+
+        for (i, resolution) in resolutions.iter().enumerate() {
+            if resolution.accepts(&states[i]) {
+                targets.entry((i, lock_id(lock))).or_insert_with(|| Target {
+                    pattern: resolution.pattern.clone(),
+                    path: path.clone(),
+                    lock,
+                });
+            }
+        }
+        for dependency in graph.dependencies_of(lock) {
+            queue.push_back((dependency, path.clone(), states.clone()));
+        }
+    }
+
+    for (i, resolution) in resolutions.iter().enumerate() {
+        if !targets.keys().any(|&(target, _)| target == i) {
+            report.problems.push(Problem::Unused { pattern: resolution.pattern.clone() });
+        }
+    }
+    for (&(i, _), target) in &targets {
+        // ranges like git urls can't be checked against a version
+        let reqs = match parse_reqs(&resolutions[i].range) {
+            Some(reqs) => reqs,
+            None => continue,
+        };
+        if !target.lock.satisfies(&reqs) {
+            report.problems.push(Problem::Violated {
+                pattern: target.pattern.clone(),
+                range:   resolutions[i].range.clone(),
+                path:    target.path.clone(),
+                found:   target.lock.version.as_ref().map(ToString::to_string).unwrap_or_default(),
+            });
+        }
+    }
+    report.targets = targets.into_values().collect();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse;
+
+    #[test]
+    fn parses_patterns() {
+        let resolution = Resolution::parse("a/**/@types/node", "14.0.0").unwrap();
+        assert_eq!(
+            resolution.segments,
+            vec![Segment::Name("a".into()), Segment::Globstar, Segment::Name("@types/node".into())]
+        );
+        assert!(resolution.matches(&["a", "@types/node"]));
+        assert!(resolution.matches(&["a", "b", "c", "@types/node"]));
+        assert!(!resolution.matches(&["b", "@types/node"]));
+        assert!(!resolution.matches(&["a", "@types/node", "c"]));
+
+        let anywhere = Resolution::parse("lodash", "4.17.21").unwrap();
+        assert!(anywhere.matches(&["lodash"]) && anywhere.matches(&["a", "lodash"]));
+        assert!(Resolution::parse("a/b", "1.0.0").unwrap().matches(&["a", "b"]));
+        assert!(!Resolution::parse("a/b", "1.0.0").unwrap().matches(&["x", "a", "b"]));
+
+        for invalid in &["a/*/b", "a/**", "a//b", "@scope"] {
+            assert!(Resolution::parse(invalid, "1.0.0").is_err(), "{} should be invalid", invalid);
+        }
+    }
+
+    #[test]
+    fn checks_resolutions() {
+        let locks = parse(r#"
+a@^1.0.0:
+  version "1.0.0"
+  dependencies:
+    lodash "^4.0.0"
+    b "^1.0.0"
+
+b@^1.0.0:
+  version "1.0.0"
+  dependencies:
+    lodash "^4.0.0"
+
+lodash@^4.0.0:
+  version "4.17.15"
+"#).unwrap();
+        let graph = Graph::new(&locks);
+        let manifest = Manifest::parse(r#"{
+            "dependencies": { "a": "^1.0.0" },
+            "resolutions": { "**/lodash": "4.17.21", "a/b": "1.0.0", "c": "1.0.0", "a/*": "1.0.0" }
+        }"#).unwrap();
+
+        let report = check(&manifest, &graph);
+        let targets = report.targets.iter().map(|target| (target.pattern.as_str(), target.path.join("/"))).collect::<Vec<_>>();
+        assert_eq!(targets, vec![("**/lodash", "a/lodash".to_string()), ("a/b", "a/b".to_string())]);
+        assert_eq!(
+            report.problems,
+            vec![
+                Problem::Invalid { pattern: "a/*".into(), reason: "only `**` is supported as glob" },
+                Problem::Unused { pattern: "c".into() },
+                Problem::Violated {
+                    pattern: "**/lodash".into(),
+                    range:   "4.17.21".into(),
+                    path:    vec!["a".into(), "lodash".into()],
+                    found:   "4.17.15".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn follows_optional_dependencies() {
+        let locks = parse(r#"
+chokidar@^2.0.0:
+  version "2.1.8"
+  optionalDependencies:
+    fsevents "^1.2.7"
+
+fsevents@^1.2.7:
+  version "1.2.7"
+"#).unwrap();
+        let graph = Graph::new(&locks);
+        let manifest = Manifest::parse(r#"{
+            "dependencies": { "chokidar": "^2.0.0" },
+            "resolutions": { "**/fsevents": "1.2.13" }
+        }"#).unwrap();
+
+        let report = check(&manifest, &graph);
+        assert_eq!(report.targets.len(), 1);
+        assert_eq!(report.targets[0].path, vec!["chokidar".to_string(), "fsevents".to_string()]);
+        assert_eq!(
+            report.problems,
+            vec![Problem::Violated {
+                pattern: "**/fsevents".into(),
+                range:   "1.2.13".into(),
+                path:    vec!["chokidar".into(), "fsevents".into()],
+                found:   "1.2.7".into(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,365 @@
+//! Resolves a dependency tree offline, from the current lock entries and a packument cache.
+//!
+//! Follows yarn v1: a descriptor that is already locked keeps its version, everything else gets the
+//! version `Packument::max_satisfying` picks, and descriptors that end up on the same version share
+//! one entry. Entries nothing depends on anymore are dropped.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+use diff::{diff, Change};
+use entry::{Descriptor, LockEntry};
+use error::Error;
+use manifest::Manifest;
+use npm_semver::parse_reqs;
+use registry::{PackageVersion, PackumentCache};
+
+/// Resolves descriptors against locked entries first and the cache second.
+pub struct Resolver<'c> {
+    cache:  &'c mut PackumentCache,
+    locked: HashMap<Descriptor, LockEntry>,
+    /// Packages whose lock entries are ignored.
+    fresh:  BTreeSet<String>,
+}
+
+impl<'c> Resolver<'c> {
+    pub fn new(cache: &'c mut PackumentCache, locked: &[LockEntry]) -> Resolver<'c> {
+        let locked = locked
+            .iter()
+            .flat_map(|entry| entry.descriptors.iter().map(move |descriptor| (descriptor.clone(), entry.clone())))
+            .collect();
+        Resolver { cache, locked, fresh: BTreeSet::new() }
+    }
+
+    /// Resolves `name` to the highest matching version again, like `yarn upgrade <name>`.
+    pub fn upgrade(&mut self, name: &str) {
+        self.fresh.insert(name.to_string());
+    }
+
+    /// The locked entry of `descriptor`, if its version still satisfies the range.
+    fn locked(&self, descriptor: &Descriptor) -> Option<LockEntry> {
+        if self.fresh.contains(&descriptor.name) {
+            return None;
+        }
+        let entry = self.locked.get(descriptor)?;
+        // git urls, tags and the like can't be checked
+        let satisfied = match (parse_reqs(&descriptor.range), entry.parsed_version()) {
+            (Some(reqs), Some(version)) => reqs.iter().any(|req| req.matches(&version)),
+            _ => true,
+        };
+        if satisfied {
+            Some(LockEntry { descriptors: Vec::new(), ..entry.clone() })
+        } else {
+            None
+        }
+    }
+
+    fn resolve_one(&mut self, descriptor: &Descriptor) -> Result<Option<LockEntry>, Error> {
+        if let Some(entry) = self.locked(descriptor) {
+            return Ok(Some(entry));
+        }
+        let found = self.cache.resolve(&descriptor.name, &descriptor.range)?;
+        Ok(found.map(PackageVersion::to_entry))
+    }
+
+    /// Resolves `roots` and all their dependencies, pairs are `(descriptor, optional)`.
+    ///
+    /// Optional dependencies, and everything below them, are left out if they can't be resolved,
+    /// anything else fails with `Error::Unresolved`.
+    fn resolve_all(&mut self, roots: Vec<(Descriptor, bool)>) -> Result<Vec<LockEntry>, Error> {
+        type Key = (String, Option<String>, Option<String>);
+        let mut entries: BTreeMap<Key, LockEntry> = BTreeMap::new();
+        let mut resolved: HashMap<Descriptor, Key> = HashMap::new();
+        // whether the dependencies of an entry were queued as required, they are queued again
+        // when an entry that was only reached optionally turns out to be required
+        let mut expanded: HashMap<Key, bool> = HashMap::new();
+        let mut queue = roots.into_iter().collect::<VecDeque<_>>();
+
+        while let Some((descriptor, optional)) = queue.pop_front() {
+            let key = match resolved.get(&descriptor) {
+                Some(key) => key.clone(),
+                None => {
+                    let entry = match self.resolve_one(&descriptor)? {
+                        Some(entry) => entry,
+                        None if optional => {
+                            warn!("skipping optional dependency {}", descriptor);
+                            continue;
+                        }
+                        None => return Err(Error::Unresolved { name: descriptor.name, range: descriptor.range }),
+                    };
+                    let key = (descriptor.name.clone(), entry.version.clone(), entry.resolved.clone());
+                    entries.entry(key.clone()).or_insert(entry).descriptors.push(descriptor.clone());
+                    resolved.insert(descriptor, key.clone());
+                    key
+                }
+            };
+
+            let required = !optional;
+            if expanded.get(&key).is_some_and(|&was_required| was_required || !required) {
+                continue;
+            }
+            expanded.insert(key.clone(), required);
+            let entry = &entries[&key];
+            let dependencies = entry.dependencies.iter().map(|dependency| (dependency, optional));
+            let optional_dependencies = entry.optional_dependencies.iter().map(|dependency| (dependency, true));
+            queue.extend(
+                dependencies
+                    .chain(optional_dependencies)
+                    .map(|((name, range), optional)| (Descriptor::new(name.as_str(), range.as_str()), optional)),
+            );
+        }
+        Ok(entries.into_values().collect())
+    }
+
+    /// Resolves `roots` and all their dependencies into a new set of lock entries.
+    pub fn resolve(&mut self, roots: &[Descriptor]) -> Result<Vec<LockEntry>, Error> {
+        self.resolve_all(roots.iter().map(|root| (root.clone(), false)).collect())
+    }
+
+    /// Resolves the `dependencies`, `devDependencies` and `optionalDependencies` of a manifest.
+    pub fn resolve_manifest(&mut self, manifest: &Manifest) -> Result<Vec<LockEntry>, Error> {
+        self.resolve_all(manifest_roots(manifest))
+    }
+}
+
+fn manifest_roots(manifest: &Manifest) -> Vec<(Descriptor, bool)> {
+    let required = manifest.dependencies.iter().chain(&manifest.dev_dependencies).map(|dep| (dep, false));
+    let optional = manifest.optional_dependencies.iter().map(|dep| (dep, true));
+    required
+        .chain(optional)
+        .map(|((name, range), optional)| (Descriptor::new(name.as_str(), range.as_str()), optional))
+        .collect()
+}
+
+/// The outcome of `upgrade`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Upgrade {
+    pub entries: Vec<LockEntry>,
+    /// What changed compared to the original entries.
+    pub changes: Vec<Change>,
+}
+
+/// Moves every descriptor of `name` that `target` satisfies to `target`, and re-resolves what changes below it.
+///
+/// New dependencies are taken from `entries` when they are already locked and from `cache` otherwise,
+/// entries that only the old version needed are removed. Everything else is left as it is.
+///
+/// The roots of the tree are the descriptors nothing in `entries` depends on, and the locked direct
+/// dependencies of the `manifest`, which the old version may have depended on as well. Without a
+/// `manifest` those can't be told apart, so it fails with `Error::ManifestRequired` if any entry would be removed.
+pub fn upgrade(
+    entries: &[LockEntry],
+    name: &str,
+    target: &PackageVersion,
+    cache: &mut PackumentCache,
+    manifest: Option<&Manifest>,
+) -> Result<Upgrade, Error> {
+    let satisfied = |descriptor: &Descriptor| {
+        parse_reqs(&descriptor.range).is_some_and(|reqs| reqs.iter().any(|req| req.matches(&target.version)))
+    };
+    let mut moved = Vec::new();
+    let mut locked = entries
+        .iter()
+        .map(|entry| {
+            if entry.name() != Some(name) {
+                return entry.clone();
+            }
+            let (upgraded, kept): (Vec<_>, Vec<_>) = entry.descriptors.iter().cloned().partition(|d| satisfied(d));
+            moved.extend(upgraded);
+            LockEntry { descriptors: kept, ..entry.clone() }
+        })
+        .collect::<Vec<_>>();
+    locked.push(LockEntry { descriptors: moved, ..target.to_entry() });
+
+    let referenced = entries
+        .iter()
+        .flat_map(|entry| entry.dependencies.iter().chain(&entry.optional_dependencies))
+        .map(|(name, range)| Descriptor::new(name.as_str(), range.as_str()))
+        .collect::<HashSet<_>>();
+    let mut roots = entries
+        .iter()
+        .flat_map(|entry| &entry.descriptors)
+        .filter(|descriptor| !referenced.contains(descriptor))
+        .map(|descriptor| (descriptor.clone(), false))
+        .collect::<Vec<_>>();
+    // only what is locked already, anything else would not be a minimal change
+    let locked_descriptors = entries.iter().flat_map(|entry| &entry.descriptors).collect::<HashSet<_>>();
+    roots.extend(
+        manifest
+            .map(manifest_roots)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(descriptor, _)| locked_descriptors.contains(descriptor)),
+    );
+
+    let upgraded = Resolver::new(cache, &locked).resolve_all(roots)?;
+    if manifest.is_none() {
+        let kept = upgraded.iter().flat_map(|entry| &entry.descriptors).collect::<HashSet<_>>();
+        let removed = locked_descriptors
+            .iter()
+            .filter(|descriptor| !kept.contains(*descriptor))
+            .map(ToString::to_string)
+            .collect::<BTreeSet<_>>();
+        if !removed.is_empty() {
+            return Err(Error::ManifestRequired { removed: removed.into_iter().collect() });
+        }
+    }
+    let locks = |entries: &[LockEntry]| entries.iter().flat_map(LockEntry::to_locks).collect::<Vec<_>>();
+    let changes = diff(&locks(entries), &locks(&upgraded));
+    Ok(Upgrade { entries: upgraded, changes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use registry::Packument;
+    use semver::Version;
+
+    fn cache() -> PackumentCache {
+        let mut cache = PackumentCache::new("/nonexistent");
+        let packuments = [
+            r#"{ "name": "a", "dist-tags": { "latest": "1.1.0" }, "versions": {
+                "1.0.0": { "dependencies": { "b": "^1.0.0" } },
+                "1.1.0": {
+                    "dependencies": { "b": "^1.0.0" },
+                    "dist": { "tarball": "https://registry.yarnpkg.com/a/-/a-1.1.0.tgz", "shasum": "aa" }
+                }
+            } }"#,
+            r#"{ "name": "b", "versions": { "1.0.0": {}, "1.2.0": {} } }"#,
+            r#"{ "name": "o", "versions": { "1.0.0": { "dependencies": { "missing": "^1.0.0" } } } }"#,
+            r#"{ "name": "p", "versions": { "1.0.0": { "dependencies": { "o": "^1.0.0" } } } }"#,
+        ];
+        for packument in &packuments {
+            cache.insert(Packument::parse(packument).unwrap());
+        }
+        cache
+    }
+
+    fn locked() -> Vec<LockEntry> {
+        let mut dependencies = BTreeMap::new();
+        dependencies.insert("b".to_string(), "^1.0.0".to_string());
+        vec![
+            LockEntry {
+                descriptors: vec![Descriptor::new("a", "^1.0.0")],
+                version: Some("1.0.0".into()),
+                dependencies,
+                ..LockEntry::default()
+            },
+            LockEntry {
+                descriptors: vec![Descriptor::new("b", "^1.0.0")],
+                version: Some("1.0.0".into()),
+                ..LockEntry::default()
+            },
+            LockEntry {
+                descriptors: vec![Descriptor::new("unused", "^1.0.0")],
+                version: Some("1.0.0".into()),
+                ..LockEntry::default()
+            },
+        ]
+    }
+
+    fn versions(entries: &[LockEntry]) -> Vec<String> {
+        entries
+            .iter()
+            .flat_map(|entry| {
+                let version = entry.version.clone().unwrap_or_default();
+                entry.descriptors.iter().map(move |descriptor| format!("{} {}", descriptor, version))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reuses_locked_entries() {
+        let manifest = Manifest::parse(r#"{
+            "dependencies": { "a": "^1.0.0" },
+            "devDependencies": { "b": "^1.1.0" },
+            "optionalDependencies": { "o": "^1.0.0" }
+        }"#).unwrap();
+        let (mut cache, locked) = (cache(), locked());
+        let entries = Resolver::new(&mut cache, &locked).resolve_manifest(&manifest).unwrap();
+        assert_eq!(versions(&entries), vec!["a@^1.0.0 1.0.0", "b@^1.0.0 1.0.0", "b@^1.1.0 1.2.0", "o@^1.0.0 1.0.0"]);
+    }
+
+    #[test]
+    fn requires_optional_packages_once_something_requires_them() {
+        let manifest = Manifest::parse(r#"{
+            "dependencies": { "p": "^1.0.0" },
+            "optionalDependencies": { "o": "^1.0.0" }
+        }"#).unwrap();
+        let mut cache = cache();
+        match Resolver::new(&mut cache, &[]).resolve_manifest(&manifest) {
+            Err(Error::Unresolved { ref name, ref range }) => assert_eq!((name.as_str(), range.as_str()), ("missing", "^1.0.0")),
+            other => panic!("expected an unresolved descriptor, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn upgrades_packages() {
+        let (mut cache, locked) = (cache(), locked());
+        let mut resolver = Resolver::new(&mut cache, &locked);
+        resolver.upgrade("a");
+        let entries = resolver.resolve(&[Descriptor::new("a", "^1.0.0")]).unwrap();
+        assert_eq!(versions(&entries), vec!["a@^1.0.0 1.1.0", "b@^1.0.0 1.0.0"]);
+        assert_eq!(entries[0].resolved, Some("https://registry.yarnpkg.com/a/-/a-1.1.0.tgz#aa".into()));
+
+        match resolver.resolve(&[Descriptor::new("b", "^2.0.0")]) {
+            Err(Error::Unresolved { ref name, ref range }) => assert_eq!((name.as_str(), range.as_str()), ("b", "^2.0.0")),
+            other => panic!("expected an unresolved descriptor, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn upgrades_one_package() {
+        let entry = |descriptors: &[&str], version: &str, dependencies: &[(&str, &str)]| LockEntry {
+            descriptors: descriptors.iter().map(|d| d.parse().unwrap()).collect(),
+            version: Some(version.into()),
+            dependencies: dependencies.iter().map(|&(name, range)| (name.into(), range.into())).collect(),
+            ..LockEntry::default()
+        };
+        let entries = vec![
+            entry(&["a@^1.0.0"], "1.0.0", &[("b", "^1.0.0")]),
+            entry(&["b@^1.0.0"], "1.0.0", &[("c", "^1.0.0")]),
+            entry(&["c@^1.0.0"], "1.0.0", &[]),
+            entry(&["e@^1.0.0"], "1.0.0", &[("c", "^1.0.0")]),
+        ];
+        let mut cache = PackumentCache::new("/nonexistent");
+        cache.insert(Packument::parse(r#"{ "name": "d", "versions": { "1.0.0": {} } }"#).unwrap());
+        let target = Packument::parse(r#"{ "name": "a", "versions": { "1.2.0": { "dependencies": { "d": "^1.0.0" } } } }"#)
+            .unwrap()
+            .versions
+            .into_values()
+            .next()
+            .unwrap();
+
+        match upgrade(&entries, "a", &target, &mut cache, None) {
+            Err(Error::ManifestRequired { ref removed }) => assert_eq!(removed, &["b@^1.0.0"]),
+            other => panic!("expected the manifest to be required, got {:?}", other),
+        }
+
+        let upgraded = upgrade(&entries, "a", &target, &mut cache, Some(&Manifest::default())).unwrap();
+        assert_eq!(versions(&upgraded.entries), vec!["a@^1.0.0 1.2.0", "c@^1.0.0 1.0.0", "d@^1.0.0 1.0.0", "e@^1.0.0 1.0.0"]);
+        let v = |v: &str| Version::parse(v).unwrap();
+        assert_eq!(
+            upgraded.changes,
+            vec![
+                Change::Changed { name: "a".into(), old: vec![v("1.0.0")], new: vec![v("1.2.0")] },
+                Change::Removed { name: "b".into(), versions: vec![v("1.0.0")] },
+                Change::Added { name: "d".into(), versions: vec![v("1.0.0")] },
+            ]
+        );
+
+        // a direct dependency that the old version depended on as well
+        let manifest = Manifest::parse(r#"{ "dependencies": { "b": "^1.0.0" } }"#).unwrap();
+        let upgraded = upgrade(&entries, "a", &target, &mut cache, Some(&manifest)).unwrap();
+        assert!(versions(&upgraded.entries).contains(&"b@^1.0.0 1.0.0".to_string()));
+
+        // nothing to remove when only the version changes
+        let target = Packument::parse(r#"{ "name": "a", "versions": { "1.1.0": { "dependencies": { "b": "^1.0.0" } } } }"#)
+            .unwrap()
+            .versions
+            .into_values()
+            .next()
+            .unwrap();
+        let upgraded = upgrade(&entries, "a", &target, &mut cache, None).unwrap();
+        assert_eq!(versions(&upgraded.entries), vec!["a@^1.0.0 1.1.0", "b@^1.0.0 1.0.0", "c@^1.0.0 1.0.0", "e@^1.0.0 1.0.0"]);
+    }
+}
@@ -0,0 +1,141 @@
+//! `serde` helpers for the fields of `DependencyLock`.
+//!
+//! Everything is written as strings, so the JSON looks like this:
+//!
+//! ```json
+//! {
+//!   "name": "chalk",
+//!   "range": "^2.0.0",
+//!   "version": "2.4.2",
+//!   "resolved": "https://registry.yarnpkg.com/chalk/-/chalk-2.4.2.tgz#cd42541677a54333cf541a49108c1432b44c9424",
+//!   "integrity": "sha512-...",
+//!   "dependencies": { "ansi-styles": "^3.2.1" },
+//!   "optionalDependencies": {}
+//! }
+//! ```
+
+use semver::{Version, VersionReq};
+use serde::de::{Deserialize, Deserializer, Error};
+use serde::ser::Serializer;
+use url::Url;
+
+use std::collections::{BTreeMap, HashMap};
+
+use npm_semver::{format_reqs, parse_reqs};
+
+fn serialize_display<T: ToString, S: Serializer>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error> {
+    match *value {
+        Some(ref value) => serializer.serialize_some(&value.to_string()),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// `Option<VersionReq>` in npm syntax, e.g. `>=1.0.0 <2`.
+pub mod opt_req {
+    use super::*;
+    use std::slice;
+
+    pub fn serialize<S: Serializer>(value: &Option<VersionReq>, serializer: S) -> Result<S::Ok, S::Error> {
+        match *value {
+            Some(ref req) => serializer.serialize_some(&format_reqs(slice::from_ref(req))),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<VersionReq>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| match parse_reqs(&s) {
+                Some(ref reqs) if reqs.len() == 1 => Ok(reqs[0].clone()),
+                _ => Err(D::Error::custom(format!("invalid range {:?}", s))),
+            })
+            .map_or(Ok(None), |r| r.map(Some))
+    }
+}
+
+/// `Option<Version>` as plain version string.
+pub mod opt_version {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &Option<Version>, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_display(value, serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Version>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| Version::parse(&s).map_err(D::Error::custom))
+            .map_or(Ok(None), |r| r.map(Some))
+    }
+}
+
+/// `Option<Url>` as string.
+pub mod opt_url {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &Option<Url>, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_display(value, serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Url>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| Url::parse(&s).map_err(D::Error::custom))
+            .map_or(Ok(None), |r| r.map(Some))
+    }
+}
+
+/// Dependencies as a sorted map from name to npm range, e.g. `^1.0.0 || ^2.0.0`.
+pub mod dependencies {
+    use super::*;
+    use serde::ser::Serialize;
+
+    pub fn serialize<S: Serializer>(value: &HashMap<String, Vec<VersionReq>>, serializer: S) -> Result<S::Ok, S::Error> {
+        value
+            .iter()
+            .map(|(name, reqs)| (name, format_reqs(reqs)))
+            .collect::<BTreeMap<_, _>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashMap<String, Vec<VersionReq>>, D::Error> {
+        HashMap::<String, String>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(name, range)| match parse_reqs(&range) {
+                Some(reqs) => Ok((name, reqs)),
+                None => Err(D::Error::custom(format!("invalid range {:?} for {}", range, name))),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use super::super::{parse, DependencyLock};
+
+    #[test]
+    fn round_trips_through_json() {
+        let locks = parse(include_str!("../yarn.lock.big")).unwrap();
+        let json = serde_json::to_string(&locks).unwrap();
+        let back: Vec<DependencyLock> = serde_json::from_str(&json).unwrap();
+        assert_eq!(locks, back);
+    }
+
+    #[test]
+    fn writes_stable_schema() {
+        let lock: DependencyLock = serde_json::from_str(r#"{
+            "name": "chalk",
+            "range": ">=2.0.0 <3",
+            "version": "2.4.2",
+            "resolved": "https://registry.yarnpkg.com/chalk/-/chalk-2.4.2.tgz",
+            "dependencies": { "semver": "^2.0.0 || >=3.1.0 <4" }
+        }"#).unwrap();
+
+        let value = serde_json::to_value(&lock).unwrap();
+        assert_eq!(value["range"], json!(">=2.0.0 <3"));
+        assert_eq!(value["integrity"], json!(null));
+        assert_eq!(
+            value["dependencies"]["semver"],
+            json!("^2.0.0 || >=3.1.0 <4")
+        );
+    }
+}
@@ -0,0 +1,136 @@
+//! Parses a `yarn.lock` from any `BufRead`, one block at a time.
+//!
+//! Only the current line and the current entry are kept in memory, so this works on huge
+//! generated lockfiles and on piped input like `git show HEAD:yarn.lock`.
+
+use std::io::BufRead;
+
+use entry::{Descriptor, LockEntry};
+use error::Error;
+use lexer::{classify, split_descriptors, Line, Section};
+
+/// Iterator over the `LockEntry`s of a `yarn.lock`.
+///
+/// Stops after the first error, which is either an I/O error or a syntax error.
+pub struct EntryReader<R> {
+    reader:  R,
+    line:    String,
+    number:  usize,
+    current: Option<LockEntry>,
+    section: Section,
+    failed:  bool,
+}
+
+impl<R: BufRead> EntryReader<R> {
+    pub fn new(reader: R) -> EntryReader<R> {
+        EntryReader {
+            reader,
+            line: String::new(),
+            number: 0,
+            current: None,
+            section: Section::None,
+            failed: false,
+        }
+    }
+
+    /// Reads lines until the next headline or the end, returning the entry that was completed.
+    fn read_entry(&mut self) -> Result<Option<LockEntry>, Error> {
+        loop {
+            self.line.clear();
+            let read = self.reader
+                .read_line(&mut self.line)
+                .map_err(|source| Error::Io { path: None, source })?;
+            if read == 0 {
+                return Ok(self.current.take());
+            }
+            self.number += 1;
+
+            let number = self.number;
+            let line = match classify(number, &self.line) {
+                Some(line) => line?,
+                None => continue,
+            };
+
+            if let Line::Headline(headline) = line {
+                let entry = LockEntry {
+                    descriptors: split_descriptors(headline)
+                        .into_iter()
+                        .map(|(name, range)| Descriptor::new(name, range))
+                        .collect(),
+                    ..LockEntry::default()
+                };
+                self.section = Section::None;
+                match self.current.replace(entry) {
+                    Some(finished) => return Ok(Some(finished)),
+                    None => continue,
+                }
+            }
+
+            let entry = self.current
+                .as_mut()
+                .ok_or_else(|| Error::syntax(number, "a headline before the first field"))?;
+            match line {
+                Line::Headline(_) => (),
+                Line::Field(key, value) => {
+                    self.section = Section::None;
+                    match key {
+                        "version" => entry.version = Some(value.to_string()),
+                        "resolved" => entry.resolved = Some(value.to_string()),
+                        "integrity" => entry.integrity = Some(value.to_string()),
+                        _ => (),
+                    }
+                }
+                Line::Section(name) => self.section = Section::named(name),
+                Line::Entry(key, value) => {
+                    let map = match self.section {
+                        Section::Dependencies => &mut entry.dependencies,
+                        Section::OptionalDependencies => &mut entry.optional_dependencies,
+                        Section::Other => continue,
+                        Section::None => return Err(Error::syntax(number, "a section like `dependencies:`")),
+                    };
+                    map.insert(key.to_string(), value.to_string());
+                }
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for EntryReader<R> {
+    type Item = Result<LockEntry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        match self.read_entry() {
+            Ok(entry) => entry.map(Ok),
+            Err(error) => {
+                self.failed = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::parse_entries;
+
+    #[test]
+    fn streams_entries() {
+        let content = include_str!("../yarn.lock.big");
+        let entries = EntryReader::new(content.as_bytes()).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(entries, parse_entries(content).unwrap());
+    }
+
+    #[test]
+    fn returns_errors() {
+        let mut reader = EntryReader::new(&b"a@^1.0.0:\n  version \"1.0.0\"\n   resolved x\n"[..]);
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+
+        let invalid_utf8 = &b"a@^1.0.0:\n  version \"\xff\"\n"[..];
+        assert!(EntryReader::new(invalid_utf8).any(|entry| entry.is_err()));
+    }
+}
@@ -0,0 +1,308 @@
+//! Checks downloaded package tarballs against the lock they were resolved for.
+//!
+//! A tarball with a valid hash can still contain the wrong package if the lockfile itself was
+//! generated from a bad mirror, so this looks at what is actually inside the archive.
+
+use flate2::read::GzDecoder;
+use semver::{Version, VersionReq};
+use tar::Archive;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use super::DependencyLock;
+use error;
+use manifest::Manifest;
+use npm_semver::{normalize, parse_reqs};
+
+/// One way in which a tarball differs from its lock.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DivergenceKind {
+    /// No tarball for this lock was found in the directory.
+    MissingTarball(PathBuf),
+    /// The tarball contains no `package/package.json`.
+    MissingManifest,
+    NameMismatch { found: Option<String> },
+    VersionMismatch { found: Option<String> },
+    /// Declared in the tarball but not locked.
+    UnlockedDependency { name: String, declared: String },
+    /// Locked but not declared in the tarball.
+    UndeclaredDependency { name: String },
+    /// Declared and locked with different ranges.
+    RangeMismatch { name: String, declared: String, locked: Vec<VersionReq> },
+}
+
+/// A divergence between a tarball and the lock of `name@version`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    pub name:    String,
+    pub version: Option<Version>,
+    pub kind:    DivergenceKind,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let version = self.version
+            .as_ref()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+        write!(f, "{}@{}: ", self.name, version)?;
+
+        match self.kind {
+            DivergenceKind::MissingTarball(ref path) => write!(f, "no tarball at {:?}", path),
+            DivergenceKind::MissingManifest => write!(f, "tarball contains no package.json"),
+            DivergenceKind::NameMismatch { ref found } => write!(f, "tarball is named {:?}", found),
+            DivergenceKind::VersionMismatch { ref found } => write!(f, "tarball has version {:?}", found),
+            DivergenceKind::UnlockedDependency { ref name, ref declared } => {
+                write!(f, "dependency {} {:?} is not locked", name, declared)
+            }
+            DivergenceKind::UndeclaredDependency { ref name } => {
+                write!(f, "locked dependency {} is not declared", name)
+            }
+            DivergenceKind::RangeMismatch { ref name, ref declared, ref locked } => {
+                let locked = locked.iter().map(ToString::to_string).collect::<Vec<_>>();
+                write!(f, "dependency {} is declared as {:?} but locked as {:?}", name, declared, locked)
+            }
+        }
+    }
+}
+
+/// File name of the tarball in an offline mirror, which is the last segment of the resolved url.
+fn tarball_name(lock: &DependencyLock) -> Option<String> {
+    if let Some(segment) = lock.resolved
+        .as_ref()
+        .and_then(|url| url.path_segments())
+        .and_then(|mut segments| segments.next_back())
+    {
+        if segment.ends_with(".tgz") {
+            // scoped packages live in `@scope/name/-/name-1.0.0.tgz`, yarn prefixes them with the scope
+            if lock.name.starts_with('@') {
+                let scope = lock.name.split('/').next().unwrap_or_default();
+                return Some(format!("{}-{}", scope, segment));
+            }
+            return Some(segment.to_string());
+        }
+    }
+
+    lock.version
+        .as_ref()
+        .map(|version| format!("{}-{}.tgz", lock.name.replace('/', "-"), version))
+}
+
+/// Reads `package/package.json` out of a `.tgz`.
+pub fn read_tarball_manifest<P: AsRef<Path>>(path: P) -> Result<Option<Manifest>, error::Error> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(error::Error::io(path))?;
+    let mut archive = Archive::new(GzDecoder::new(file));
+
+    for entry in archive.entries().map_err(error::Error::io(path))? {
+        let mut entry = entry.map_err(error::Error::io(path))?;
+        // most tarballs use `package/`, but the top level folder name is not enforced by npm
+        let is_manifest = {
+            let inner = entry.path().map_err(error::Error::io(path))?;
+            inner.components().count() == 2 && inner.ends_with("package.json")
+        };
+        if is_manifest {
+            let mut content = String::new();
+            entry.read_to_string(&mut content).map_err(error::Error::io(path))?;
+            return Manifest::parse(&content).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// Whether two requirements allow the same versions, however they are spelled.
+fn same_range(a: &[VersionReq], b: &[VersionReq]) -> bool {
+    match (normalize(a), normalize(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn compare_dependencies(
+    locked: &HashMap<String, Vec<VersionReq>>,
+    declared: &BTreeMap<String, String>,
+) -> Vec<DivergenceKind> {
+    let mut kinds = Vec::new();
+
+    for (name, range) in declared {
+        // locks only keep semver ranges, git urls and tags have nothing to compare with
+        let reqs = match parse_reqs(range) {
+            Some(reqs) => reqs,
+            None => continue,
+        };
+        match locked.get(name) {
+            None => kinds.push(DivergenceKind::UnlockedDependency {
+                name:     name.clone(),
+                declared: range.clone(),
+            }),
+            Some(locked) => {
+                if !same_range(&reqs, locked) {
+                    kinds.push(DivergenceKind::RangeMismatch {
+                        name:     name.clone(),
+                        declared: range.clone(),
+                        locked:   locked.clone(),
+                    })
+                }
+            }
+        }
+    }
+
+    let mut undeclared = locked
+        .keys()
+        .filter(|name| !declared.contains_key(*name))
+        .cloned()
+        .collect::<Vec<_>>();
+    undeclared.sort();
+    kinds.extend(undeclared.into_iter().map(|name| DivergenceKind::UndeclaredDependency { name }));
+
+    kinds
+}
+
+/// Compares one lock with the manifest found in its tarball.
+pub fn compare(lock: &DependencyLock, manifest: &Manifest) -> Vec<DivergenceKind> {
+    let mut kinds = Vec::new();
+
+    if manifest.name.as_ref() != Some(&lock.name) {
+        kinds.push(DivergenceKind::NameMismatch { found: manifest.name.clone() });
+    }
+
+    let found_version = manifest.version.as_ref().and_then(|v| Version::parse(v).ok());
+    if found_version.is_none() || found_version != lock.version {
+        kinds.push(DivergenceKind::VersionMismatch { found: manifest.version.clone() });
+    }
+
+    kinds.extend(compare_dependencies(&lock.dependencies, &manifest.dependencies));
+    kinds.extend(compare_dependencies(&lock.optional_dependencies, &manifest.optional_dependencies));
+    kinds
+}
+
+/// Verifies every lock against its tarball in `dir`, usually a yarn offline mirror.
+///
+/// Locks that share a version, because they came from the same block, are only checked once.
+pub fn verify_tarballs<P: AsRef<Path>>(dir: P, locks: &[DependencyLock]) -> Result<Vec<Divergence>, error::Error> {
+    let dir = dir.as_ref();
+    let mut seen = HashSet::new();
+    let mut divergences = Vec::new();
+
+    for lock in locks {
+        if !seen.insert((&lock.name, &lock.version)) {
+            continue;
+        }
+        let file_name = match tarball_name(lock) {
+            Some(file_name) => file_name,
+            None => continue,
+        };
+
+        let path = dir.join(file_name);
+        let kinds = if !path.is_file() {
+            vec![DivergenceKind::MissingTarball(path)]
+        } else {
+            debug!("verifying {:?}", path);
+            match read_tarball_manifest(&path)? {
+                Some(manifest) => compare(lock, &manifest),
+                None => vec![DivergenceKind::MissingManifest],
+            }
+        };
+
+        divergences.extend(kinds.into_iter().map(|kind| Divergence {
+            name:    lock.name.clone(),
+            version: lock.version.clone(),
+            kind,
+        }));
+    }
+
+    Ok(divergences)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use tar::{Builder, Header};
+
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    fn lock() -> DependencyLock {
+        let mut dependencies = HashMap::new();
+        dependencies.insert("js-tokens".to_string(), parse_reqs("^4.0.0").unwrap());
+        let mut optional_dependencies = HashMap::new();
+        optional_dependencies.insert("fsevents".to_string(), parse_reqs("^1.2.7").unwrap());
+        DependencyLock {
+            name:         "@babel/highlight".into(),
+            last_seen:    None,
+            version:      Version::parse("7.9.0").ok(),
+            resolved:     "https://registry.yarnpkg.com/@babel/highlight/-/highlight-7.9.0.tgz#4e9b45".parse().ok(),
+            integrity:    None,
+            dependencies,
+            optional_dependencies,
+        }
+    }
+
+    #[test]
+    fn names_tarballs_like_the_offline_mirror() {
+        assert_eq!(tarball_name(&lock()), Some("@babel-highlight-7.9.0.tgz".into()));
+    }
+
+    #[test]
+    fn compares_manifest() {
+        let manifest = Manifest::parse(r#"{
+            "name": "@babel/highlight",
+            "version": "7.9.1",
+            "dependencies": { "js-tokens": "^4.0.0", "chalk": "^2.0.0" },
+            "optionalDependencies": { "fsevents": "^2.0.0" }
+        }"#).unwrap();
+
+        assert_eq!(
+            compare(&lock(), &manifest),
+            vec![
+                DivergenceKind::VersionMismatch { found: Some("7.9.1".into()) },
+                DivergenceKind::UnlockedDependency { name: "chalk".into(), declared: "^2.0.0".into() },
+                DivergenceKind::RangeMismatch {
+                    name:     "fsevents".into(),
+                    declared: "^2.0.0".into(),
+                    locked:   parse_reqs("^1.2.7").unwrap(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn verifies_tarballs_in_a_directory() {
+        let dir = env::temp_dir().join(format!("yarn-lock-tarballs-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let manifest = br#"{
+            "name": "@babel/highlight",
+            "version": "7.9.0",
+            "dependencies": { "js-tokens": ">=4.0.0 <5.0.0-0", "chalk": "github:chalk/chalk" },
+            "optionalDependencies": { "fsevents": "^1.2.7" }
+        }"#;
+        let mut header = Header::new_gnu();
+        header.set_path("package/package.json").unwrap();
+        header.set_size(manifest.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        let file = File::create(dir.join("@babel-highlight-7.9.0.tgz")).unwrap();
+        let mut builder = Builder::new(GzEncoder::new(file, Compression::default()));
+        builder.append(&header, &manifest[..]).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let mut missing = lock();
+        missing.name = "left-pad".into();
+        missing.resolved = None;
+        let divergences = verify_tarballs(&dir, &[lock(), missing]).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(divergences.len(), 1);
+        assert_eq!(divergences[0].name, "left-pad");
+        assert_eq!(divergences[0].kind, DivergenceKind::MissingTarball(dir.join("left-pad-7.9.0.tgz")));
+    }
+}
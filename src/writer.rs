@@ -0,0 +1,131 @@
+//! Writes `yarn.lock` files, formatted the same way yarn v1 does.
+
+use serde_json;
+
+use std::collections::BTreeMap;
+
+use entry::LockEntry;
+
+const HEADER: &str = "# THIS IS AN AUTOGENERATED FILE. DO NOT EDIT THIS FILE DIRECTLY.\n# yarn lockfile v1\n";
+
+/// Mirrors yarn's `shouldWrapKey`, which is applied to keys and values alike.
+fn needs_quotes(s: &str) -> bool {
+    s.starts_with("true")
+        || s.starts_with("false")
+        || s.chars().any(|c| c.is_whitespace() || ":\\\",[]".contains(c))
+        || !s.chars().next().map(|c| c.is_ascii_alphabetic()).unwrap_or(false)
+}
+
+fn maybe_quote(s: &str) -> String {
+    if needs_quotes(s) {
+        serde_json::to_string(s).unwrap_or_else(|_| format!("{:?}", s))
+    } else {
+        s.to_string()
+    }
+}
+
+fn write_map(out: &mut String, key: &str, map: &BTreeMap<String, String>) {
+    if map.is_empty() {
+        return;
+    }
+    out.push_str(&format!("\n  {}:", key));
+    for (name, range) in map {
+        out.push_str(&format!("\n    {} {}", maybe_quote(name), maybe_quote(range)));
+    }
+}
+
+impl LockEntry {
+    fn sorted_descriptors(&self) -> Vec<String> {
+        let mut descriptors = self.descriptors.iter().map(ToString::to_string).collect::<Vec<_>>();
+        descriptors.sort();
+        descriptors.dedup();
+        descriptors
+    }
+
+    /// The entry as one block, without a trailing newline.
+    pub fn to_yarn_lock(&self) -> String {
+        let mut out = self.sorted_descriptors()
+            .iter()
+            .map(|descriptor| maybe_quote(descriptor))
+            .collect::<Vec<_>>()
+            .join(", ");
+        out.push(':');
+
+        let fields = [("version", &self.version), ("resolved", &self.resolved), ("integrity", &self.integrity)];
+        for &(key, value) in &fields {
+            if let Some(ref value) = *value {
+                out.push_str(&format!("\n  {} {}", key, maybe_quote(value)));
+            }
+        }
+        write_map(&mut out, "dependencies", &self.dependencies);
+        write_map(&mut out, "optionalDependencies", &self.optional_dependencies);
+        out
+    }
+}
+
+/// Formats a whole `yarn.lock`, entries are sorted by their first descriptor.
+pub fn to_yarn_lock(entries: &[LockEntry]) -> String {
+    let mut entries = entries.iter().map(|entry| (entry.sorted_descriptors(), entry)).collect::<Vec<_>>();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let body = entries
+        .into_iter()
+        .map(|(_, entry)| entry.to_yarn_lock())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    format!("{}\n\n{}\n", HEADER, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use entry::Descriptor;
+
+    #[test]
+    fn quotes_like_yarn() {
+        assert_eq!(maybe_quote("chalk@^2.0.0"), "chalk@^2.0.0");
+        assert_eq!(maybe_quote("@babel/highlight"), "\"@babel/highlight\"");
+        assert_eq!(maybe_quote("fstream@>= 0.1.30 < 1"), "\"fstream@>= 0.1.30 < 1\"");
+        assert_eq!(maybe_quote("7.8.3"), "\"7.8.3\"");
+        assert_eq!(maybe_quote("sha512-Mti+f9lpJNcwF4tWV8/OrTTt=="), "sha512-Mti+f9lpJNcwF4tWV8/OrTTt==");
+    }
+
+    #[test]
+    fn writes_yarn_lock() {
+        let mut dependencies = BTreeMap::new();
+        dependencies.insert("js-tokens".to_string(), "^4.0.0".to_string());
+        let entries = [
+            LockEntry {
+                descriptors: vec![Descriptor::new("js-tokens", "^4.0.0")],
+                version: Some("4.0.0".into()),
+                ..LockEntry::default()
+            },
+            LockEntry {
+                descriptors: vec![Descriptor::new("@babel/highlight", "^7.8.3"), Descriptor::new("@babel/highlight", "^7.0.0")],
+                version: Some("7.9.0".into()),
+                resolved: Some("https://registry.yarnpkg.com/@babel/highlight/-/highlight-7.9.0.tgz#4e9b45".into()),
+                integrity: Some("sha512-lJZPilxX7Op3Nv==".into()),
+                dependencies,
+                ..LockEntry::default()
+            },
+        ];
+
+        assert_eq!(
+            to_yarn_lock(&entries),
+            r#"# THIS IS AN AUTOGENERATED FILE. DO NOT EDIT THIS FILE DIRECTLY.
+# yarn lockfile v1
+
+
+"@babel/highlight@^7.0.0", "@babel/highlight@^7.8.3":
+  version "7.9.0"
+  resolved "https://registry.yarnpkg.com/@babel/highlight/-/highlight-7.9.0.tgz#4e9b45"
+  integrity sha512-lJZPilxX7Op3Nv==
+  dependencies:
+    js-tokens "^4.0.0"
+
+js-tokens@^4.0.0:
+  version "4.0.0"
+"#
+        );
+    }
+}